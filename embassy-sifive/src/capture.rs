@@ -0,0 +1,337 @@
+//! Input capture on GPIO edges
+//!
+//! The FE310 PWM blocks have no input capture mode, so edges are timestamped
+//! in the GPIO interrupt instead and pushed into a [`CaptureBuffer`].
+//!
+//! Two timebases are available:
+//! - `mcycle` counts core clock cycles, giving sub-µs resolution.
+//! - `mtime` counts the 32.768 kHz low-frequency clock, which is independent
+//!   of the core clock and better suited for long spans.
+
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::mem::ManuallyDrop;
+use core::ptr;
+use core::task::Poll;
+
+use e310x::CLINT;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::CriticalSectionMutex as Mutex;
+use embassy_sync::waitqueue::AtomicWaker;
+use portable_atomic::{AtomicBool, AtomicPtr, Ordering};
+use riscv::register::mcycle;
+
+use crate::clock::{Clocks, MTIME_HZ};
+use crate::gpio::{self, Edge, InterruptPin, IntoUnknown};
+use crate::interrupt::typelevel::Binding;
+
+/// Number of edges a [`CaptureBuffer`] can hold
+pub const CAPTURE_DEPTH: usize = 16;
+
+/// Counter used to timestamp edges
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Timebase {
+    /// Core clock cycle counter
+    Mcycle,
+    /// Machine timer, driven by the low-frequency clock
+    Mtime,
+}
+
+/// Timestamped edge
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EdgeTimestamp {
+    /// Edge that was captured, [`Edge::Both`] if two edges came within the
+    /// interrupt latency
+    pub edge: Edge,
+    /// Value of the timebase when the edge was handled
+    pub ticks: u64,
+}
+
+/// Time between two captured edges
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Span {
+    /// Length in timebase ticks
+    pub ticks: u64,
+    /// Frequency of the timebase
+    pub tick_hz: u32,
+}
+
+impl Span {
+    /// Returns the length in nanoseconds
+    pub fn as_nanos(&self) -> u64 {
+        self.scale(1_000_000_000)
+    }
+
+    /// Returns the length in microseconds
+    pub fn as_micros(&self) -> u64 {
+        self.scale(1_000_000)
+    }
+
+    fn scale(&self, unit: u64) -> u64 {
+        let hz = self.tick_hz as u64;
+        (self.ticks / hz) * unit + ((self.ticks % hz) * unit) / hz
+    }
+}
+
+struct Ring {
+    entries: [EdgeTimestamp; CAPTURE_DEPTH],
+    head: usize,
+    len: usize,
+    overrun: bool,
+}
+
+impl Ring {
+    const fn new() -> Self {
+        Ring {
+            entries: [EdgeTimestamp {
+                edge: Edge::Both,
+                ticks: 0,
+            }; CAPTURE_DEPTH],
+            head: 0,
+            len: 0,
+            overrun: false,
+        }
+    }
+
+    fn push(&mut self, entry: EdgeTimestamp) {
+        if self.len == CAPTURE_DEPTH {
+            self.overrun = true;
+            return;
+        }
+        self.entries[(self.head + self.len) % CAPTURE_DEPTH] = entry;
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<EdgeTimestamp> {
+        if self.len == 0 {
+            return None;
+        }
+        let entry = self.entries[self.head];
+        self.head = (self.head + 1) % CAPTURE_DEPTH;
+        self.len -= 1;
+        Some(entry)
+    }
+
+    fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+        self.overrun = false;
+    }
+}
+
+/// Ring buffer of timestamped edges, filled by the GPIO interrupt
+pub struct CaptureBuffer {
+    ring: Mutex<RefCell<Ring>>,
+    waker: AtomicWaker,
+    use_mtime: AtomicBool,
+}
+
+impl CaptureBuffer {
+    /// Creates an empty buffer
+    pub const fn new() -> Self {
+        CaptureBuffer {
+            ring: Mutex::const_new(CriticalSectionRawMutex::new(), RefCell::new(Ring::new())),
+            waker: AtomicWaker::new(),
+            use_mtime: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Default for CaptureBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_BUFFER: AtomicPtr<CaptureBuffer> = AtomicPtr::new(core::ptr::null_mut());
+/// Capture buffers attached to the GPIO interrupts, one per pin
+static BUFFERS: [AtomicPtr<CaptureBuffer>; 32] = [NO_BUFFER; 32];
+
+fn on_edge(index: usize, edge: Edge) {
+    let buffer = BUFFERS[index].load(Ordering::SeqCst);
+    if buffer.is_null() {
+        return;
+    }
+    let buffer = unsafe { &*buffer };
+
+    let ticks = if buffer.use_mtime.load(Ordering::Relaxed) {
+        CLINT::mtime().read()
+    } else {
+        mcycle::read64()
+    };
+
+    critical_section::with(|cs| {
+        buffer
+            .ring
+            .borrow(cs)
+            .borrow_mut()
+            .push(EdgeTimestamp { edge, ticks })
+    });
+    buffer.waker.wake();
+}
+
+/// Input capture on a GPIO pin.
+///
+/// Dropping the capture stops it and returns the pin to its reset state.
+pub struct InputCapture<PIN: InterruptPin> {
+    pin: PIN,
+    buffer: &'static CaptureBuffer,
+    edge: Edge,
+    tick_hz: u32,
+}
+
+impl<PIN: InterruptPin> InputCapture<PIN> {
+    /// Starts timestamping `edge` transitions on `pin` into `buffer`.
+    ///
+    /// [`InputCapture::pulse_width`] requires `edge` to be [`Edge::Both`].
+    pub fn new(
+        mut pin: PIN,
+        _irq: impl Binding<PIN::Interrupt, gpio::InterruptHandler>,
+        edge: Edge,
+        timebase: Timebase,
+        buffer: &'static CaptureBuffer,
+        clocks: Clocks,
+    ) -> Self {
+        let index = pin.index();
+        let tick_hz = match timebase {
+            Timebase::Mcycle => clocks.coreclk().0,
+            Timebase::Mtime => MTIME_HZ,
+        };
+
+        buffer
+            .use_mtime
+            .store(timebase == Timebase::Mtime, Ordering::Relaxed);
+        critical_section::with(|cs| buffer.ring.borrow(cs).borrow_mut().clear());
+        BUFFERS[index].store(buffer as *const _ as *mut _, Ordering::SeqCst);
+
+        gpio::set_interrupt_handler::<PIN::Interrupt>(on_edge);
+        pin.enable_interrupt(edge);
+
+        InputCapture {
+            pin,
+            buffer,
+            edge,
+            tick_hz,
+        }
+    }
+
+    /// Returns the frequency of the selected timebase
+    pub fn tick_hz(&self) -> u32 {
+        self.tick_hz
+    }
+
+    /// Returns `true` if edges were dropped because the buffer was full
+    pub fn overrun(&self) -> bool {
+        critical_section::with(|cs| self.buffer.ring.borrow(cs).borrow().overrun)
+    }
+
+    /// Discards all buffered edges and the overrun flag
+    pub fn clear(&mut self) {
+        critical_section::with(|cs| self.buffer.ring.borrow(cs).borrow_mut().clear());
+    }
+
+    /// Returns the oldest buffered edge, if any
+    pub fn try_next(&mut self) -> Option<EdgeTimestamp> {
+        critical_section::with(|cs| self.buffer.ring.borrow(cs).borrow_mut().pop())
+    }
+
+    /// Waits for the next captured edge
+    pub async fn next_edge(&mut self) -> EdgeTimestamp {
+        poll_fn(|cx| {
+            self.buffer.waker.register(cx.waker());
+
+            match self.try_next() {
+                Some(entry) => Poll::Ready(entry),
+                None => Poll::Pending,
+            }
+        })
+        .await
+    }
+
+    /// Measures the next pulse that begins with a `start` edge.
+    ///
+    /// Use [`Edge::Rising`] for a high pulse and [`Edge::Falling`] for a low one.
+    /// Buffered edges are discarded first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capture was not created with [`Edge::Both`], as the end
+    /// of the pulse would never be captured.
+    pub async fn pulse_width(&mut self, start: Edge) -> Span {
+        assert!(
+            self.edge == Edge::Both,
+            "pulse_width needs a capture of both edges"
+        );
+        self.clear();
+
+        let mut begin = None;
+        loop {
+            let entry = self.next_edge().await;
+            match (begin, entry.edge) {
+                (_, e) if e == start => begin = Some(entry.ticks),
+                (Some(begin), Edge::Rising | Edge::Falling) => {
+                    return self.span(begin, entry.ticks)
+                }
+                // Pulse shorter than the interrupt latency, start over
+                _ => begin = None,
+            }
+        }
+    }
+
+    /// Measures the time between two consecutive edges of the same kind.
+    ///
+    /// Buffered edges are discarded first.
+    pub async fn period(&mut self) -> Span {
+        self.clear();
+
+        let mut first = self.next_edge().await;
+        loop {
+            let entry = self.next_edge().await;
+            if entry.edge == first.edge && entry.edge != Edge::Both {
+                return self.span(first.ticks, entry.ticks);
+            }
+            if first.edge == Edge::Both {
+                first = entry;
+            }
+        }
+    }
+
+    /// Stops capturing and releases the pin in its reset state, see
+    /// [`IntoUnknown`]
+    pub fn free(self) -> PIN::Unknown {
+        self.release().into_unknown()
+    }
+
+    /// Stops capturing and releases the pin, still configured as an input
+    pub(crate) fn release(self) -> PIN {
+        let mut this = ManuallyDrop::new(self);
+        this.stop();
+        // `this` is never dropped, so the pin is moved out only once
+        unsafe { ptr::read(&this.pin) }
+    }
+
+    fn stop(&mut self) {
+        let index = self.pin.index();
+        self.pin.disable_interrupt();
+        gpio::clear_interrupt_handler::<PIN::Interrupt>();
+        BUFFERS[index].store(ptr::null_mut(), Ordering::SeqCst);
+    }
+
+    fn span(&self, begin: u64, end: u64) -> Span {
+        Span {
+            ticks: end.wrapping_sub(begin),
+            tick_hz: self.tick_hz,
+        }
+    }
+}
+
+impl<PIN: InterruptPin> Drop for InputCapture<PIN> {
+    fn drop(&mut self) {
+        self.stop();
+        self.pin.reset();
+    }
+}
@@ -15,6 +15,9 @@ const PLLOUT_MAX: u32 = 384_000_000;
 const DIVOUT_MIN: u32 = 375_000;
 const DIVOUT_MAX: u32 = 384_000_000;
 
+/// `mtime` runs from the 32.768 kHz low-frequency clock
+pub(crate) const MTIME_HZ: u32 = 32_768;
+
 /// Configures clock generation system.
 ///
 /// For HiFive1 and HiFive1 Rev B boards external oscillators are enabled for
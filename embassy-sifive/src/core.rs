@@ -0,0 +1,67 @@
+//! E31 core peripherals
+
+use riscv::register::{mcycle, minstret};
+
+pub use e310x::{CLINT, PLIC};
+
+/// Opaque mcycle register
+pub struct MCYCLE;
+
+impl MCYCLE {
+    /// Reads the mcycle and mcycleh registers
+    #[inline]
+    pub fn value(&self) -> u64 {
+        mcycle::read64()
+    }
+}
+
+/// Opaque minstret register
+pub struct MINSTRET;
+
+impl MINSTRET {
+    /// Reads the minstret and minstreth registers
+    #[inline]
+    pub fn value(&self) -> u64 {
+        minstret::read64()
+    }
+}
+
+/// Performance counters
+pub struct PerformanceCounters {
+    /// 64-bit mcycle counter
+    pub mcycle: MCYCLE,
+    /// 64-bit minstret counter
+    pub minstret: MINSTRET,
+}
+
+/// Core peripherals
+pub struct CorePeripherals {
+    /// Core-Local Interruptor
+    pub clint: CLINT,
+    /// Platform-Level Interrupt Controller
+    pub plic: PLIC,
+    /// Performance counters
+    pub counters: PerformanceCounters,
+}
+
+impl CorePeripherals {
+    pub(crate) fn new() -> Self {
+        CorePeripherals {
+            clint: CLINT,
+            plic: PLIC,
+            counters: PerformanceCounters {
+                mcycle: MCYCLE,
+                minstret: MINSTRET,
+            },
+        }
+    }
+
+    /// Steals the core peripherals
+    ///
+    /// # Safety
+    ///
+    /// Using this function may break the guarantees of the singleton pattern.
+    pub unsafe fn steal() -> Self {
+        Self::new()
+    }
+}
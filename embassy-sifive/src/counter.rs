@@ -0,0 +1,148 @@
+//! GPIO pulse counter and frequency meter
+//!
+//! Edges on the input pin are counted in the GPIO interrupt. Frequencies are
+//! measured by counting over a gate window timed with `mtime`.
+
+use core::mem::ManuallyDrop;
+use core::ptr;
+
+use e310x::CLINT;
+use embassy_time::{Duration, Timer};
+use portable_atomic::{AtomicU32, Ordering};
+
+use crate::clock::MTIME_HZ;
+use crate::gpio::{self, Edge, InterruptPin, IntoUnknown};
+use crate::interrupt::typelevel::Binding;
+use crate::time::Hertz;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU32 = AtomicU32::new(0);
+/// Pulses counted by the GPIO interrupts, one counter per pin
+static COUNTS: [AtomicU32; 32] = [ZERO; 32];
+
+fn on_edge(index: usize, edge: Edge) {
+    // Both edges were pending if they came faster than the interrupt latency
+    let pulses = if edge == Edge::Both { 2 } else { 1 };
+    COUNTS[index].fetch_add(pulses, Ordering::Relaxed);
+}
+
+/// Result of a gated measurement
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Measurement {
+    /// Pulses counted during the gate window
+    pub pulses: u32,
+    /// Actual length of the gate window, in `mtime` ticks
+    pub ticks: u64,
+}
+
+impl Measurement {
+    /// Returns the measured frequency in millihertz
+    pub fn millihertz(&self) -> u64 {
+        if self.ticks == 0 {
+            return 0;
+        }
+        self.pulses as u64 * MTIME_HZ as u64 * 1_000 / self.ticks
+    }
+
+    /// Returns the measured frequency, rounded down to whole hertz
+    pub fn hertz(&self) -> Hertz {
+        Hertz((self.millihertz() / 1_000) as u32)
+    }
+}
+
+/// Pulse counter on a GPIO input.
+///
+/// Dropping the counter stops it and returns the pin to its reset state.
+pub struct PulseCounter<PIN: InterruptPin> {
+    pin: PIN,
+    gate: Duration,
+}
+
+impl<PIN: InterruptPin> PulseCounter<PIN> {
+    /// Starts counting `edge` transitions on `pin`.
+    ///
+    /// `gate` is the window used by [`PulseCounter::measure`].
+    pub fn new(
+        mut pin: PIN,
+        _irq: impl Binding<PIN::Interrupt, gpio::InterruptHandler>,
+        edge: Edge,
+        gate: Duration,
+    ) -> Self {
+        COUNTS[pin.index()].store(0, Ordering::Relaxed);
+        gpio::set_interrupt_handler::<PIN::Interrupt>(on_edge);
+        pin.enable_interrupt(edge);
+
+        PulseCounter { pin, gate }
+    }
+
+    /// Returns the number of pulses counted since the last reset
+    pub fn count(&self) -> u32 {
+        COUNTS[self.pin.index()].load(Ordering::Relaxed)
+    }
+
+    /// Resets the pulse count to zero
+    pub fn reset(&mut self) {
+        COUNTS[self.pin.index()].store(0, Ordering::Relaxed);
+    }
+
+    /// Returns the number of pulses counted since the last reset and resets it
+    pub fn take(&mut self) -> u32 {
+        COUNTS[self.pin.index()].swap(0, Ordering::Relaxed)
+    }
+
+    /// Changes the gate window used for frequency measurements
+    pub fn set_gate(&mut self, gate: Duration) {
+        self.gate = gate;
+    }
+
+    /// Counts pulses over one gate window
+    pub async fn measure(&mut self) -> Measurement {
+        let index = self.pin.index();
+
+        let start = critical_section::with(|_| {
+            COUNTS[index].store(0, Ordering::Relaxed);
+            CLINT::mtime().read()
+        });
+
+        Timer::after(self.gate).await;
+
+        let (pulses, end) = critical_section::with(|_| {
+            (
+                COUNTS[index].swap(0, Ordering::Relaxed),
+                CLINT::mtime().read(),
+            )
+        });
+
+        Measurement {
+            pulses,
+            ticks: end - start,
+        }
+    }
+
+    /// Measures the input frequency over one gate window
+    pub async fn frequency(&mut self) -> Hertz {
+        self.measure().await.hertz()
+    }
+
+    /// Stops counting and releases the pin in its reset state, see
+    /// [`IntoUnknown`]
+    pub fn free(self) -> PIN::Unknown {
+        let mut this = ManuallyDrop::new(self);
+        this.stop();
+        // `this` is never dropped, so the pin is moved out only once
+        unsafe { ptr::read(&this.pin) }.into_unknown()
+    }
+
+    fn stop(&mut self) {
+        self.pin.disable_interrupt();
+        gpio::clear_interrupt_handler::<PIN::Interrupt>();
+    }
+}
+
+impl<PIN: InterruptPin> Drop for PulseCounter<PIN> {
+    fn drop(&mut self) {
+        self.stop();
+        self.pin.reset();
+    }
+}
@@ -0,0 +1,220 @@
+//! Debounced input pins
+//!
+//! [`Debouncer`] wraps any [`InputPin`] and samples it with `embassy-time`.
+//! A level change is only accepted once the input has been stable for the
+//! configured interval, and every accepted change is reported as an
+//! [`Event`] with the [`Instant`] at which the edge was first seen.
+
+use embassy_time::{Duration, Instant, Timer};
+use embedded_hal::digital::InputPin;
+
+use crate::gpio::IntoUnknown;
+
+/// Debouncer configuration
+#[derive(Clone, Copy)]
+pub struct Config {
+    /// Time the input must remain at a new level before the change is accepted
+    pub stable_interval: Duration,
+    /// Interval between two consecutive samples of the pin
+    pub sample_interval: Duration,
+    /// The input reads low while pressed (e.g. a button to ground with a pull-up)
+    pub active_low: bool,
+    /// Report [`EventKind::LongPress`] once the input is held for this long
+    pub long_press: Option<Duration>,
+    /// Report [`EventKind::DoubleClick`] when two presses start within this window
+    pub double_click: Option<Duration>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            stable_interval: Duration::from_millis(20),
+            sample_interval: Duration::from_millis(1),
+            active_low: true,
+            long_press: None,
+            double_click: None,
+        }
+    }
+}
+
+/// Kind of debounced input event
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EventKind {
+    /// The input became active
+    Press,
+    /// The input became inactive
+    Release,
+    /// The input has been active for [`Config::long_press`]
+    LongPress,
+    /// The second of two presses within [`Config::double_click`]
+    DoubleClick,
+}
+
+/// Debounced input event
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Event {
+    /// What happened
+    pub kind: EventKind,
+    /// When it happened
+    pub timestamp: Instant,
+}
+
+/// Debounced input pin
+pub struct Debouncer<PIN> {
+    pin: PIN,
+    config: Config,
+    pressed: bool,
+    /// Start of the current press, `None` if no press edge was observed, for
+    /// instance when the input was already active at construction
+    pressed_at: Option<Instant>,
+    long_press_reported: bool,
+    last_press: Option<Instant>,
+    pending: Option<Event>,
+}
+
+impl<PIN: InputPin> Debouncer<PIN> {
+    /// Creates a new debouncer, taking the current pin level as the initial state
+    pub fn new(mut pin: PIN, config: Config) -> Result<Self, PIN::Error> {
+        let pressed = pin.is_high()? != config.active_low;
+
+        Ok(Debouncer {
+            pin,
+            config,
+            pressed,
+            pressed_at: None,
+            long_press_reported: false,
+            last_press: None,
+            pending: None,
+        })
+    }
+
+    /// Returns the current debounced state of the input
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Waits for the next debounced event
+    pub async fn next_event(&mut self) -> Result<Event, PIN::Error> {
+        if let Some(event) = self.pending.take() {
+            return Ok(event);
+        }
+
+        loop {
+            if let Some(event) = self.check_long_press() {
+                return Ok(event);
+            }
+
+            if self.raw_pressed()? != self.pressed {
+                let timestamp = Instant::now();
+                if self.wait_stable(!self.pressed).await? {
+                    return Ok(self.accept_edge(timestamp));
+                }
+            }
+
+            Timer::after(self.config.sample_interval).await;
+        }
+    }
+
+    /// Releases the wrapped pin as it was passed in, the debouncer never
+    /// reconfigures it
+    pub fn into_inner(self) -> PIN {
+        self.pin
+    }
+
+    fn raw_pressed(&mut self) -> Result<bool, PIN::Error> {
+        Ok(self.pin.is_high()? != self.config.active_low)
+    }
+
+    /// Samples the pin until it has stayed at `level` for the stable interval.
+    /// Returns `false` if it bounced back in the meantime.
+    async fn wait_stable(&mut self, level: bool) -> Result<bool, PIN::Error> {
+        let deadline = Instant::now() + self.config.stable_interval;
+
+        while Instant::now() < deadline {
+            Timer::after(self.config.sample_interval).await;
+            if self.raw_pressed()? != level {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn accept_edge(&mut self, timestamp: Instant) -> Event {
+        self.pressed = !self.pressed;
+
+        if !self.pressed {
+            self.pressed_at = None;
+            return Event {
+                kind: EventKind::Release,
+                timestamp,
+            };
+        }
+
+        self.pressed_at = Some(timestamp);
+        self.long_press_reported = false;
+
+        match (self.config.double_click, self.last_press) {
+            (Some(window), Some(last)) if timestamp - last <= window => {
+                // Both clicks are consumed, a third press starts a new pair
+                self.last_press = None;
+                self.pending = Some(Event {
+                    kind: EventKind::DoubleClick,
+                    timestamp,
+                });
+            }
+            _ => self.last_press = Some(timestamp),
+        }
+
+        Event {
+            kind: EventKind::Press,
+            timestamp,
+        }
+    }
+
+    fn check_long_press(&mut self) -> Option<Event> {
+        let hold = self.config.long_press?;
+
+        if let (Some(pressed_at), false) = (self.pressed_at, self.long_press_reported) {
+            let timestamp = pressed_at + hold;
+            if Instant::now() >= timestamp {
+                self.long_press_reported = true;
+                return Some(Event {
+                    kind: EventKind::LongPress,
+                    timestamp,
+                });
+            }
+        }
+
+        None
+    }
+}
+
+impl<PIN: InputPin + IntoUnknown> Debouncer<PIN> {
+    /// Releases the wrapped pin in its reset state, see [`IntoUnknown`]
+    pub fn free(self) -> PIN::Unknown {
+        self.pin.into_unknown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, Debouncer};
+    use crate::gpio::mock::{bit, set_bit};
+    use crate::gpio::{GpioExt, Register};
+
+    #[test]
+    fn free_resets_pin() {
+        let pins = unsafe { e310x::Gpio0::steal() }.split();
+        set_bit(Register::InputVal, 8, true);
+
+        let debouncer = Debouncer::new(pins.pin8.into_pull_up_input(), Config::default()).unwrap();
+        assert!(!debouncer.is_pressed());
+
+        let _pin = debouncer.free();
+        assert!(!bit(Register::Pullup, 8));
+        assert!(!bit(Register::InputEn, 8));
+    }
+}
@@ -0,0 +1,92 @@
+//! FIFOs and watermark interrupts of the UART and SPI blocks
+//!
+//! Both blocks have 8-entry TX and RX FIFOs. `txwm` is pending while the TX
+//! FIFO holds fewer entries than its watermark and `rxwm` while the RX FIFO
+//! holds more; the conditions stay pending until the FIFOs are serviced.
+
+use core::ops::Deref;
+
+use e310x::{qspi0, uart0};
+
+/// Depth of the TX and RX FIFOs
+pub(crate) const FIFO_DEPTH: usize = 8;
+
+/// Byte shifted out while only receiving
+pub(crate) const DUMMY_BYTE: u8 = 0x00;
+
+/// `ie` and `ip` registers of a block with watermark interrupts
+pub(crate) trait Watermarks {
+    /// Returns the `txwm` and `rxwm` bits of `ip`
+    fn pending(&self) -> (bool, bool);
+
+    /// Replaces the `txwm` and `rxwm` bits of `ie` by `f` of their value
+    fn modify_enabled(&self, f: impl FnOnce(bool, bool) -> (bool, bool));
+}
+
+impl Watermarks for uart0::RegisterBlock {
+    fn pending(&self) -> (bool, bool) {
+        let ip = self.ip().read();
+        (ip.txwm().bit_is_set(), ip.rxwm().bit_is_set())
+    }
+
+    fn modify_enabled(&self, f: impl FnOnce(bool, bool) -> (bool, bool)) {
+        self.ie().modify(|r, w| {
+            let (tx, rx) = f(r.txwm().bit_is_set(), r.rxwm().bit_is_set());
+            w.txwm().bit(tx).rxwm().bit(rx)
+        });
+    }
+}
+
+impl Watermarks for qspi0::RegisterBlock {
+    fn pending(&self) -> (bool, bool) {
+        let ip = self.ip().read();
+        (ip.txwm().bit_is_set(), ip.rxwm().bit_is_set())
+    }
+
+    fn modify_enabled(&self, f: impl FnOnce(bool, bool) -> (bool, bool)) {
+        self.ie().modify(|r, w| {
+            let (tx, rx) = f(r.txwm().bit_is_set(), r.rxwm().bit_is_set());
+            w.txwm().bit(tx).rxwm().bit(rx)
+        });
+    }
+}
+
+/// Handles the watermark interrupts of `regs` and returns which of `txwm`
+/// and `rxwm` fired.
+///
+/// The fired interrupts are disabled, as they would fire again right away;
+/// the woken task re-enables what it needs.
+pub(crate) fn on_interrupt<R>(regs: &R) -> (bool, bool)
+where
+    R: Deref,
+    R::Target: Watermarks,
+{
+    let (tx_pending, rx_pending) = regs.pending();
+    let mut fired = (false, false);
+    regs.modify_enabled(|tx, rx| {
+        fired = (tx && tx_pending, rx && rx_pending);
+        (tx && !tx_pending, rx && !rx_pending)
+    });
+    fired
+}
+
+/// Enables the TX watermark interrupt.
+///
+/// The halves of a driver may live in different tasks, so `ie` is only
+/// modified inside a critical section.
+pub(crate) fn listen_tx<R>(regs: &R)
+where
+    R: Deref,
+    R::Target: Watermarks,
+{
+    critical_section::with(|_| regs.modify_enabled(|_, rx| (true, rx)));
+}
+
+/// Enables the RX watermark interrupt
+pub(crate) fn listen_rx<R>(regs: &R)
+where
+    R: Deref,
+    R::Target: Watermarks,
+{
+    critical_section::with(|_| regs.modify_enabled(|tx, _| (tx, true)));
+}
@@ -0,0 +1,979 @@
+//! QSPI0 flash interface
+//!
+//! QSPI0 is wired to the boot flash, which the core executes from through
+//! the memory-mapped (execute-in-place, XIP) read path. The read command used
+//! by that path is set by `ffmt` and clocked by `sckdiv`; the boot ROM leaves
+//! a conservative single-lane setup behind.
+//!
+//! [`Flash`] erases and programs the flash. It leaves XIP mode, sends JEDEC
+//! commands through the SPI FIFOs and re-enters XIP mode once the flash is
+//! idle again. Reads go through the memory map. The capacity, the erase
+//! commands, the fast read commands and the quad enable method are read from
+//! the SFDP tables of the flash, so [`Flash`] and [`Flash::xip_config`] adapt
+//! to the chip without per-chip code.
+//!
+//! While the flash interface is being switched or written the flash cannot
+//! be read, so the routines doing so are placed in `.data`, which the runtime
+//! copies to RAM at startup, and run with interrupts disabled. They only use
+//! raw register accesses and never call back into code located in flash.
+//! Their helpers are `#[inline(always)]`, which is honoured at every
+//! optimization level, and memory accesses and fences are written as inline
+//! assembly, since `core::ptr::write_volatile`, `*ptr.add(i)` and friends
+//! turn into calls into flash at `opt-level = 0`.
+
+use core::ptr;
+
+use e310x::Qspi0;
+use embedded_storage::nor_flash::{
+    ErrorType, MultiwriteNorFlash, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+use crate::clock::Clocks;
+use crate::fifo::DUMMY_BYTE;
+use crate::spi::sck_divisor;
+use crate::time::Hertz;
+
+mod sfdp;
+pub use sfdp::{EraseType, FastRead, Parameters, QuadEnable, SfdpError};
+
+/// QSPI0 register offsets
+mod reg {
+    pub const SCKDIV: usize = 0x00;
+    pub const CSMODE: usize = 0x18;
+    pub const FMT: usize = 0x40;
+    pub const TXDATA: usize = 0x48;
+    pub const RXDATA: usize = 0x4c;
+    pub const FCTRL: usize = 0x60;
+    pub const FFMT: usize = 0x64;
+}
+
+/// JEDEC command opcodes
+mod cmd {
+    pub const READ_ID: u8 = 0x9f;
+    pub const READ_STATUS: u8 = 0x05;
+    pub const READ_STATUS_2: u8 = 0x35;
+    pub const READ_STATUS_2_ALT: u8 = 0x3f;
+    pub const WRITE_STATUS: u8 = 0x01;
+    pub const WRITE_STATUS_2: u8 = 0x31;
+    pub const WRITE_STATUS_2_ALT: u8 = 0x3e;
+    pub const WRITE_ENABLE: u8 = 0x06;
+    pub const READ_SFDP: u8 = 0x5a;
+    pub const SECTOR_ERASE: u8 = 0x20;
+    pub const BLOCK_ERASE: u8 = 0xd8;
+    pub const PAGE_PROGRAM: u8 = 0x02;
+}
+
+/// Start of the memory-mapped flash
+const MEMORY_BASE: usize = 0x2000_0000;
+
+/// Largest `ffmt.pad_cnt` value
+const MAX_DUMMY_CYCLES: u8 = 15;
+
+/// `csmode` values
+const CSMODE_AUTO: u32 = 0;
+const CSMODE_HOLD: u32 = 2;
+
+/// `fmt` for commands: single lane, MSB first, receive, 8-bit frames
+const FMT_COMMAND: u32 = 8 << 16;
+
+/// `txdata.full` and `rxdata.empty`
+const FIFO_FLAG: u32 = 1 << 31;
+
+/// Status register write-in-progress bit
+const STATUS_WIP: u8 = 1 << 0;
+
+/// Page program size
+const PAGE_SIZE: usize = 256;
+/// Sector erase size
+const SECTOR_SIZE: usize = 4096;
+/// Block erase size
+const BLOCK_SIZE: usize = 65536;
+
+/// Largest flash reachable with 3-byte addresses
+const MAX_CAPACITY: usize = 1 << 24;
+
+/// Writes a QSPI0 register
+#[inline(always)]
+unsafe fn write_reg(offset: usize, value: u32) {
+    let address = Qspi0::PTR as usize + offset;
+    #[cfg(target_arch = "riscv32")]
+    core::arch::asm!(
+        "sw {value}, 0({address})",
+        value = in(reg) value,
+        address = in(reg) address,
+        options(nostack, preserves_flags),
+    );
+    #[cfg(not(target_arch = "riscv32"))]
+    ptr::write_volatile(address as *mut u32, value);
+}
+
+/// Reads a QSPI0 register
+#[inline(always)]
+unsafe fn read_reg(offset: usize) -> u32 {
+    let address = Qspi0::PTR as usize + offset;
+    #[cfg(target_arch = "riscv32")]
+    {
+        let value: u32;
+        core::arch::asm!(
+            "lw {value}, 0({address})",
+            value = out(reg) value,
+            address = in(reg) address,
+            options(nostack, preserves_flags),
+        );
+        value
+    }
+    #[cfg(not(target_arch = "riscv32"))]
+    ptr::read_volatile(address as *const u32)
+}
+
+/// Loads the byte at `address`
+#[inline(always)]
+unsafe fn load_byte(address: usize) -> u8 {
+    #[cfg(target_arch = "riscv32")]
+    {
+        let value: u8;
+        core::arch::asm!(
+            "lbu {value}, 0({address})",
+            value = out(reg) value,
+            address = in(reg) address,
+            options(nostack, preserves_flags),
+        );
+        value
+    }
+    #[cfg(not(target_arch = "riscv32"))]
+    ptr::read(address as *const u8)
+}
+
+/// Stores `value` at `address`
+#[inline(always)]
+unsafe fn store_byte(address: usize, value: u8) {
+    #[cfg(target_arch = "riscv32")]
+    core::arch::asm!(
+        "sb {value}, 0({address})",
+        value = in(reg) value,
+        address = in(reg) address,
+        options(nostack, preserves_flags),
+    );
+    #[cfg(not(target_arch = "riscv32"))]
+    ptr::write(address as *mut u8, value);
+}
+
+/// Orders the register accesses before and after it
+#[inline(always)]
+unsafe fn fence() {
+    #[cfg(target_arch = "riscv32")]
+    core::arch::asm!("fence", options(nostack, preserves_flags));
+    #[cfg(not(target_arch = "riscv32"))]
+    core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+}
+
+/// Number of data lines used by a phase of a flash command
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Protocol {
+    /// One line, MOSI out and MISO in
+    Single = 0,
+    /// Two bidirectional lines
+    Dual = 1,
+    /// Four bidirectional lines
+    Quad = 2,
+}
+
+/// Read command used by the memory-mapped flash path
+#[derive(Clone, Copy)]
+pub struct XipConfig {
+    /// Command opcode, always sent on a single line
+    pub opcode: u8,
+    /// Lines used for the 3-byte address
+    pub address: Protocol,
+    /// Lines used for the data
+    pub data: Protocol,
+    /// Clock cycles between the address and the data, mode bits included
+    pub dummy_cycles: u8,
+    /// Maximum SCK frequency the flash supports for this command
+    pub max_frequency: Hertz,
+}
+
+impl XipConfig {
+    /// `0x0B` fast read, 1-1-1 with 8 dummy cycles
+    pub const fn fast_read(max_frequency: Hertz) -> Self {
+        Self::new(0x0b, Protocol::Single, Protocol::Single, 8, max_frequency)
+    }
+
+    /// `0x3B` dual output fast read, 1-1-2 with 8 dummy cycles
+    pub const fn dual_output(max_frequency: Hertz) -> Self {
+        Self::new(0x3b, Protocol::Single, Protocol::Dual, 8, max_frequency)
+    }
+
+    /// `0xBB` dual I/O fast read, 1-2-2 with 4 dummy cycles
+    pub const fn dual_io(max_frequency: Hertz) -> Self {
+        Self::new(0xbb, Protocol::Dual, Protocol::Dual, 4, max_frequency)
+    }
+
+    /// `0x6B` quad output fast read, 1-1-4 with 8 dummy cycles.
+    ///
+    /// The quad enable bit of the flash must be set.
+    pub const fn quad_output(max_frequency: Hertz) -> Self {
+        Self::new(0x6b, Protocol::Single, Protocol::Quad, 8, max_frequency)
+    }
+
+    /// `0xEB` quad I/O fast read, 1-4-4 with 6 dummy cycles.
+    ///
+    /// The quad enable bit of the flash must be set.
+    pub const fn quad_io(max_frequency: Hertz) -> Self {
+        Self::new(0xeb, Protocol::Quad, Protocol::Quad, 6, max_frequency)
+    }
+
+    const fn new(
+        opcode: u8,
+        address: Protocol,
+        data: Protocol,
+        dummy_cycles: u8,
+        max_frequency: Hertz,
+    ) -> Self {
+        XipConfig {
+            opcode,
+            address,
+            data,
+            dummy_cycles,
+            max_frequency,
+        }
+    }
+}
+
+/// Flash interface configuration error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// The SCK frequency is below what the divisor can reach from the bus clock
+    FrequencyOutOfRange,
+    /// More dummy cycles than `ffmt` can insert
+    TooManyDummyCycles,
+    /// Quad mode requested, but the flash does not tell how to enable it
+    QuadNotSupported,
+}
+
+/// Encodes `config` as an `ffmt` value with 3-byte addresses.
+///
+/// Dummy cycles shift out zeros, which also keeps the flash out of
+/// continuous read mode.
+fn ffmt_bits(config: &XipConfig) -> Result<u32, ConfigError> {
+    if config.dummy_cycles > MAX_DUMMY_CYCLES {
+        return Err(ConfigError::TooManyDummyCycles);
+    }
+
+    let cmd_en = 1;
+    let addr_len = 3 << 1;
+    let pad_cnt = (config.dummy_cycles as u32) << 4;
+    let cmd_proto = (Protocol::Single as u32) << 8;
+    let addr_proto = (config.address as u32) << 10;
+    let data_proto = (config.data as u32) << 12;
+    let cmd_code = (config.opcode as u32) << 16;
+
+    Ok(cmd_en | addr_len | pad_cnt | cmd_proto | addr_proto | data_proto | cmd_code)
+}
+
+/// Switches the memory-mapped read path to `ffmt` clocked with `sckdiv`.
+///
+/// # Safety
+///
+/// Must be called with interrupts disabled. The flash must support the
+/// command encoded in `ffmt` at the resulting SCK frequency.
+#[inline(never)]
+#[link_section = ".data.ramfunc.apply_xip"]
+unsafe fn apply_xip(ffmt: u32, sckdiv: u32) {
+    // No flash read is in flight while executing from RAM
+    write_reg(reg::FCTRL, 0);
+    write_reg(reg::SCKDIV, sckdiv);
+    write_reg(reg::FFMT, ffmt);
+    write_reg(reg::FCTRL, 1);
+    fence();
+}
+
+/// Configures the memory-mapped flash read path for the current `clocks`.
+///
+/// SCK runs at the highest frequency allowed by `config.max_frequency`.
+/// Call it again whenever the bus clock changes. Returns the SCK frequency.
+pub fn configure_xip(
+    _qspi: &mut Qspi0,
+    config: &XipConfig,
+    clocks: Clocks,
+) -> Result<Hertz, ConfigError> {
+    let ffmt = ffmt_bits(config)?;
+    let (sckdiv, frequency) = sck_divisor(clocks.tlclk(), config.max_frequency)
+        .map_err(|_| ConfigError::FrequencyOutOfRange)?;
+
+    riscv::interrupt::free(|| unsafe { apply_xip(ffmt, sckdiv) });
+    Ok(frequency)
+}
+
+// Helpers of the RAM routines. Between `begin` and `end` the flash cannot be
+// read, so these must only be called from RAM routines.
+
+/// Leaves XIP mode and drops stale bytes from the RX FIFO
+#[inline(always)]
+unsafe fn begin() {
+    write_reg(reg::FCTRL, 0);
+    write_reg(reg::FMT, FMT_COMMAND);
+    while read_reg(reg::RXDATA) & FIFO_FLAG == 0 {}
+}
+
+/// Re-enters XIP mode
+#[inline(always)]
+unsafe fn end() {
+    write_reg(reg::FCTRL, 1);
+    fence();
+}
+
+/// Asserts CS until [`deselect`]
+#[inline(always)]
+unsafe fn select() {
+    write_reg(reg::CSMODE, CSMODE_HOLD);
+}
+
+/// Releases CS, leaving it under the control of the XIP engine
+#[inline(always)]
+unsafe fn deselect() {
+    write_reg(reg::CSMODE, CSMODE_AUTO);
+}
+
+/// Shifts out `byte` and returns the byte shifted in meanwhile
+#[inline(always)]
+unsafe fn transfer(byte: u8) -> u8 {
+    while read_reg(reg::TXDATA) & FIFO_FLAG != 0 {}
+    write_reg(reg::TXDATA, byte as u32);
+    loop {
+        let rxdata = read_reg(reg::RXDATA);
+        if rxdata & FIFO_FLAG == 0 {
+            return rxdata as u8;
+        }
+    }
+}
+
+/// Selects the flash and sends `opcode` followed by a 3-byte address
+#[inline(always)]
+unsafe fn command_with_address(opcode: u8, address: u32) {
+    select();
+    transfer(opcode);
+    transfer((address >> 16) as u8);
+    transfer((address >> 8) as u8);
+    transfer(address as u8);
+}
+
+/// Sets the write enable latch
+#[inline(always)]
+unsafe fn write_enable() {
+    select();
+    transfer(cmd::WRITE_ENABLE);
+    deselect();
+}
+
+/// Polls the status register until the pending erase or program completes
+#[inline(always)]
+unsafe fn wait_ready() {
+    loop {
+        select();
+        transfer(cmd::READ_STATUS);
+        let status = transfer(DUMMY_BYTE);
+        deselect();
+        if status & STATUS_WIP == 0 {
+            break;
+        }
+    }
+}
+
+/// Sends `opcode` and reads `len` response bytes into `response`.
+///
+/// # Safety
+///
+/// Must be called with interrupts disabled, `response` must be valid for
+/// `len` bytes.
+#[inline(never)]
+#[link_section = ".data.ramfunc.flash_read_register"]
+unsafe fn ram_read_register(opcode: u8, response: *mut u8, len: usize) {
+    begin();
+    select();
+    transfer(opcode);
+    let mut i = 0;
+    while i < len {
+        store_byte(response as usize + i, transfer(DUMMY_BYTE));
+        i += 1;
+    }
+    deselect();
+    end();
+}
+
+/// Reads `len` bytes of the SFDP area from `address` into `buf`.
+///
+/// # Safety
+///
+/// Must be called with interrupts disabled, `buf` must be valid for `len`
+/// bytes.
+#[inline(never)]
+#[link_section = ".data.ramfunc.flash_read_sfdp"]
+unsafe fn ram_read_sfdp(address: u32, buf: *mut u8, len: usize) {
+    begin();
+    command_with_address(cmd::READ_SFDP, address);
+    // 8 dummy cycles
+    transfer(DUMMY_BYTE);
+    let mut i = 0;
+    while i < len {
+        store_byte(buf as usize + i, transfer(DUMMY_BYTE));
+        i += 1;
+    }
+    deselect();
+    end();
+}
+
+/// Writes `len` bytes from `data` to a register of the flash through
+/// `opcode` and waits for the write to complete.
+///
+/// # Safety
+///
+/// Must be called with interrupts disabled, `data` must be valid for `len`
+/// bytes and located in RAM.
+#[inline(never)]
+#[link_section = ".data.ramfunc.flash_write_register"]
+unsafe fn ram_write_register(opcode: u8, data: *const u8, len: usize) {
+    begin();
+    write_enable();
+    select();
+    transfer(opcode);
+    let mut i = 0;
+    while i < len {
+        transfer(load_byte(data as usize + i));
+        i += 1;
+    }
+    deselect();
+    wait_ready();
+    end();
+}
+
+/// Erases the sector or block at `address` with `opcode` and waits for the
+/// erase to complete.
+///
+/// # Safety
+///
+/// Must be called with interrupts disabled.
+#[inline(never)]
+#[link_section = ".data.ramfunc.flash_erase"]
+unsafe fn ram_erase(opcode: u8, address: u32) {
+    begin();
+    write_enable();
+    command_with_address(opcode, address);
+    deselect();
+    wait_ready();
+    end();
+}
+
+/// Programs `len` bytes from `data` at `address` and waits for the program
+/// to complete. The bytes must not cross a page boundary.
+///
+/// # Safety
+///
+/// Must be called with interrupts disabled, `data` must be valid for `len`
+/// bytes and located in RAM.
+#[inline(never)]
+#[link_section = ".data.ramfunc.flash_program"]
+unsafe fn ram_program(address: u32, data: *const u8, len: usize) {
+    begin();
+    write_enable();
+    command_with_address(cmd::PAGE_PROGRAM, address);
+    let mut i = 0;
+    while i < len {
+        transfer(load_byte(data as usize + i));
+        i += 1;
+    }
+    deselect();
+    wait_ready();
+    end();
+}
+
+/// Returns the capacity encoded in the last byte of a JEDEC ID.
+///
+/// ISSI, GigaDevice and Winbond all encode it as a power of two.
+fn capacity_from_id(id: [u8; 3]) -> Option<usize> {
+    let capacity = 1usize.checked_shl(id[2] as u32)?;
+    (BLOCK_SIZE..=MAX_CAPACITY)
+        .contains(&capacity)
+        .then_some(capacity)
+}
+
+/// Returns the quad enable method of the manufacturer in a JEDEC ID, for
+/// SFDP tables that predate the quad enable requirements
+fn quad_enable_from_id(id: [u8; 3]) -> Option<QuadEnable> {
+    match id[0] {
+        // ISSI
+        0x9d => Some(QuadEnable::Sr1Bit6),
+        // Winbond, GigaDevice
+        0xef | 0xc8 => Some(QuadEnable::Sr2Bit1),
+        _ => None,
+    }
+}
+
+/// Completes the parameters read from the SFDP tables of the flash with its
+/// JEDEC ID, or derives them from the ID alone if it has no usable tables
+fn identify(id: [u8; 3], sfdp: Result<Parameters, SfdpError>) -> Option<Parameters> {
+    let mut parameters = match sfdp {
+        Ok(parameters) => parameters,
+        Err(SfdpError::NotPresent | SfdpError::NoBasicTable) => Parameters::from_jedec_id(id)?,
+        Err(SfdpError::FourByteAddressing) => return None,
+    };
+    // A density the driver cannot work with is more likely a bogus table
+    if !(BLOCK_SIZE..=MAX_CAPACITY).contains(&parameters.capacity) {
+        parameters.capacity = capacity_from_id(id)?;
+    }
+    parameters.quad_enable = parameters.quad_enable.or_else(|| quad_enable_from_id(id));
+
+    Some(parameters).filter(|parameters| parameters.erase_type(SECTOR_SIZE).is_some())
+}
+
+/// Returns the fastest read command described by `parameters`, see
+/// [`Flash::xip_config`]
+fn xip_config(parameters: &Parameters, max_frequency: Hertz) -> XipConfig {
+    let Parameters {
+        quad_enable,
+        quad_io,
+        quad_output,
+        dual_io,
+        dual_output,
+        ..
+    } = *parameters;
+    let (quad_io, quad_output) = match quad_enable {
+        Some(_) => (quad_io, quad_output),
+        None => (None, None),
+    };
+    let candidates = [
+        (quad_io, Protocol::Quad, Protocol::Quad),
+        (quad_output, Protocol::Single, Protocol::Quad),
+        (dual_io, Protocol::Dual, Protocol::Dual),
+        (dual_output, Protocol::Single, Protocol::Dual),
+    ];
+
+    candidates
+        .into_iter()
+        .find_map(|(read, address, data)| {
+            let read = read.filter(|read| read.dummy_cycles <= MAX_DUMMY_CYCLES)?;
+            Some(XipConfig::new(
+                read.opcode,
+                address,
+                data,
+                read.dummy_cycles,
+                max_frequency,
+            ))
+        })
+        .unwrap_or(XipConfig::fast_read(max_frequency))
+}
+
+/// Returns the largest erase command of `parameters` that starts at
+/// `address` and stays below `to`
+fn largest_erase(parameters: &Parameters, address: usize, to: usize) -> Option<EraseType> {
+    parameters
+        .erase_types
+        .iter()
+        .flatten()
+        .filter(|erase| address % erase.size == 0 && to - address >= erase.size)
+        .max_by_key(|erase| erase.size)
+        .copied()
+}
+
+/// Flash driver error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Neither the SFDP tables nor the JEDEC ID describe a supported flash
+    UnsupportedDevice([u8; 3]),
+    /// Offset or length not aligned to the erase size
+    NotAligned,
+    /// Range outside of the flash
+    OutOfBounds,
+}
+
+impl NorFlashError for Error {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            Error::NotAligned => NorFlashErrorKind::NotAligned,
+            Error::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            Error::UnsupportedDevice(_) => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+/// NOR flash connected to QSPI0.
+///
+/// Offsets are relative to the start of the flash, which is also where the
+/// program is stored: the application must keep its data out of the range
+/// used by the image. Interrupts are disabled while a sector or a page is
+/// being written, which takes up to a few hundred milliseconds for a block
+/// erase.
+pub struct Flash {
+    qspi: Qspi0,
+    id: [u8; 3],
+    parameters: Parameters,
+}
+
+impl Flash {
+    /// Identifies the flash connected to `qspi` from its SFDP tables, or
+    /// from its JEDEC ID if it has none.
+    ///
+    /// SFDP is read at the current SCK frequency, which must not exceed
+    /// 50 MHz: call this before speeding up the read path.
+    pub fn new(qspi: Qspi0) -> Result<Self, Error> {
+        let mut id = [0; 3];
+        read_register(cmd::READ_ID, &mut id);
+
+        let parameters =
+            identify(id, sfdp::parse(read_sfdp)).ok_or(Error::UnsupportedDevice(id))?;
+
+        Ok(Flash {
+            qspi,
+            id,
+            parameters,
+        })
+    }
+
+    /// Returns the JEDEC manufacturer and device ID
+    pub fn jedec_id(&self) -> [u8; 3] {
+        self.id
+    }
+
+    /// Returns the detected flash parameters
+    pub fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+
+    /// Reads the status register
+    pub fn read_status(&mut self) -> u8 {
+        read_register_byte(cmd::READ_STATUS)
+    }
+
+    /// Sets the quad enable bit of the flash, as described by its SFDP
+    /// tables or, for older tables, known from its manufacturer. The bit is
+    /// non-volatile and only written if it is clear.
+    pub fn enable_quad(&mut self) -> Result<(), ConfigError> {
+        let method = self
+            .parameters
+            .quad_enable
+            .ok_or(ConfigError::QuadNotSupported)?;
+
+        match method {
+            QuadEnable::NotRequired => {}
+            QuadEnable::Sr1Bit6 => {
+                let status = self.read_status();
+                if status & (1 << 6) == 0 {
+                    write_register(cmd::WRITE_STATUS, &[status | (1 << 6)]);
+                }
+            }
+            QuadEnable::Sr2Bit7 => {
+                let status = read_register_byte(cmd::READ_STATUS_2_ALT);
+                if status & (1 << 7) == 0 {
+                    write_register(cmd::WRITE_STATUS_2_ALT, &[status | (1 << 7)]);
+                }
+            }
+            QuadEnable::Sr2Bit1 => {
+                let status_2 = read_register_byte(cmd::READ_STATUS_2);
+                if status_2 & (1 << 1) == 0 {
+                    let status = self.read_status();
+                    write_register(cmd::WRITE_STATUS, &[status, status_2 | (1 << 1)]);
+                }
+            }
+            QuadEnable::Sr2Bit1WriteSr2 => {
+                let status_2 = read_register_byte(cmd::READ_STATUS_2);
+                if status_2 & (1 << 1) == 0 {
+                    write_register(cmd::WRITE_STATUS_2, &[status_2 | (1 << 1)]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the fastest read command of the flash for the memory-mapped
+    /// read path.
+    ///
+    /// SFDP does not describe the frequency the flash supports, so it is
+    /// given as `max_frequency`. Quad commands are only used if it is known
+    /// how to enable quad mode.
+    pub fn xip_config(&self, max_frequency: Hertz) -> XipConfig {
+        xip_config(&self.parameters, max_frequency)
+    }
+
+    /// Configures the memory-mapped read path, see [`configure_xip`].
+    ///
+    /// Quad mode is enabled first if `config` uses quad lines. Fails with
+    /// [`ConfigError::QuadNotSupported`], leaving the read path unchanged, if
+    /// the quad enable method of the flash is not known.
+    pub fn configure_xip(
+        &mut self,
+        config: &XipConfig,
+        clocks: Clocks,
+    ) -> Result<Hertz, ConfigError> {
+        let quad = config.address == Protocol::Quad || config.data == Protocol::Quad;
+        if quad {
+            self.enable_quad()?;
+        }
+        configure_xip(&mut self.qspi, config, clocks)
+    }
+
+    /// Releases the QSPI0 peripheral
+    pub fn free(self) -> Qspi0 {
+        self.qspi
+    }
+
+    fn check_range(&self, offset: u32, len: usize) -> Result<(), Error> {
+        match (offset as usize).checked_add(len) {
+            Some(end) if end <= self.parameters.capacity => Ok(()),
+            _ => Err(Error::OutOfBounds),
+        }
+    }
+}
+
+/// Reads a register of the flash through `opcode`
+fn read_register(opcode: u8, response: &mut [u8]) {
+    riscv::interrupt::free(|| unsafe {
+        ram_read_register(opcode, response.as_mut_ptr(), response.len())
+    });
+}
+
+/// Reads a one-byte register of the flash through `opcode`
+fn read_register_byte(opcode: u8) -> u8 {
+    let mut value = [0];
+    read_register(opcode, &mut value);
+    value[0]
+}
+
+/// Writes up to two bytes to a register of the flash through `opcode`
+fn write_register(opcode: u8, data: &[u8]) {
+    // `data` may be a constant located in flash
+    let mut buf = [0; 2];
+    let buf = &mut buf[..data.len()];
+    buf.copy_from_slice(data);
+    riscv::interrupt::free(|| unsafe { ram_write_register(opcode, buf.as_ptr(), buf.len()) });
+}
+
+/// Reads from the SFDP area of the flash
+fn read_sfdp(address: u32, buf: &mut [u8]) {
+    riscv::interrupt::free(|| unsafe { ram_read_sfdp(address, buf.as_mut_ptr(), buf.len()) });
+}
+
+impl ErrorType for Flash {
+    type Error = Error;
+}
+
+impl ReadNorFlash for Flash {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Error> {
+        self.check_range(offset, bytes.len())?;
+
+        let src = (MEMORY_BASE + offset as usize) as *const u8;
+        unsafe { ptr::copy_nonoverlapping(src, bytes.as_mut_ptr(), bytes.len()) };
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.parameters.capacity
+    }
+}
+
+impl NorFlash for Flash {
+    const WRITE_SIZE: usize = 1;
+    const ERASE_SIZE: usize = SECTOR_SIZE;
+
+    /// Erases `from..to`, using the largest erase commands the range allows
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Error> {
+        if from > to {
+            return Err(Error::OutOfBounds);
+        }
+        self.check_range(from, (to - from) as usize)?;
+        if from as usize % SECTOR_SIZE != 0 || to as usize % SECTOR_SIZE != 0 {
+            return Err(Error::NotAligned);
+        }
+
+        let mut address = from as usize;
+        let to = to as usize;
+        while address < to {
+            // The 4 KiB erase always fits
+            let erase = largest_erase(&self.parameters, address, to).ok_or(Error::NotAligned)?;
+            riscv::interrupt::free(|| unsafe { ram_erase(erase.opcode, address as u32) });
+            address += erase.size;
+        }
+        Ok(())
+    }
+
+    /// Programs `bytes` at `offset`, one page at a time
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Error> {
+        self.check_range(offset, bytes.len())?;
+
+        // `bytes` may itself be located in flash
+        let mut page = [0; PAGE_SIZE];
+        let mut address = offset as usize;
+        let mut bytes = bytes;
+        while !bytes.is_empty() {
+            let len = (PAGE_SIZE - address % PAGE_SIZE).min(bytes.len());
+            let (chunk, rest) = bytes.split_at(len);
+            page[..len].copy_from_slice(chunk);
+            riscv::interrupt::free(|| unsafe { ram_program(address as u32, page.as_ptr(), len) });
+            address += len;
+            bytes = rest;
+        }
+        Ok(())
+    }
+}
+
+// Programming only clears bits, so a word can be written several times
+impl MultiwriteNorFlash for Flash {}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        capacity_from_id, ffmt_bits, identify, largest_erase, sfdp, xip_config, ConfigError,
+        FastRead, Parameters, Protocol, QuadEnable, SfdpError, XipConfig,
+    };
+    use crate::time::Hertz;
+
+    /// Parameters of an ISSI IS25LP128 as read from its SFDP tables
+    fn is25lp128() -> Parameters {
+        let sfdp = sfdp::tests::is25lp128();
+        sfdp::parse(sfdp::tests::reader(&sfdp)).unwrap()
+    }
+
+    fn read_command(config: XipConfig) -> (u8, Protocol, Protocol, u8) {
+        (
+            config.opcode,
+            config.address,
+            config.data,
+            config.dummy_cycles,
+        )
+    }
+
+    #[test]
+    fn ffmt_encoding() {
+        // Boot ROM default: 0x03 read, no dummy cycles, all single
+        let read = XipConfig {
+            dummy_cycles: 0,
+            opcode: 0x03,
+            ..XipConfig::fast_read(Hertz(50_000_000))
+        };
+        assert_eq!(ffmt_bits(&read), Ok(0x0003_0007));
+
+        assert_eq!(
+            ffmt_bits(&XipConfig::quad_io(Hertz(104_000_000))),
+            Ok(0x00eb_2867)
+        );
+        assert_eq!(
+            ffmt_bits(&XipConfig::dual_output(Hertz(104_000_000))),
+            Ok(0x003b_1087)
+        );
+    }
+
+    #[test]
+    fn dummy_cycles_limit() {
+        let config = XipConfig {
+            dummy_cycles: 16,
+            ..XipConfig::fast_read(Hertz(50_000_000))
+        };
+        assert_eq!(ffmt_bits(&config), Err(ConfigError::TooManyDummyCycles));
+    }
+
+    #[test]
+    fn capacity_from_jedec_id() {
+        // ISSI IS25LP128
+        assert_eq!(capacity_from_id([0x9d, 0x60, 0x18]), Some(16 << 20));
+        // GigaDevice GD25Q32
+        assert_eq!(capacity_from_id([0xc8, 0x40, 0x16]), Some(4 << 20));
+        // 32 MB parts need 4-byte addresses
+        assert_eq!(capacity_from_id([0xef, 0x40, 0x19]), None);
+        assert_eq!(capacity_from_id([0xff, 0xff, 0xff]), None);
+    }
+
+    #[test]
+    fn xip_config_selection() {
+        let frequency = Hertz(50_000_000);
+        let mut parameters = is25lp128();
+        assert_eq!(
+            read_command(xip_config(&parameters, frequency)),
+            (0xeb, Protocol::Quad, Protocol::Quad, 6)
+        );
+
+        // Quad reads need a known quad enable method
+        parameters.quad_enable = None;
+        assert_eq!(
+            read_command(xip_config(&parameters, frequency)),
+            (0xbb, Protocol::Dual, Protocol::Dual, 4)
+        );
+
+        // More dummy cycles than ffmt can insert
+        parameters.dual_io = Some(FastRead {
+            opcode: 0xbb,
+            dummy_cycles: 16,
+        });
+        assert_eq!(
+            read_command(xip_config(&parameters, frequency)),
+            (0x3b, Protocol::Single, Protocol::Dual, 8)
+        );
+
+        parameters.dual_output = None;
+        assert_eq!(
+            read_command(xip_config(&parameters, frequency)),
+            (0x0b, Protocol::Single, Protocol::Single, 8)
+        );
+    }
+
+    #[test]
+    fn erase_selection() {
+        let parameters = is25lp128();
+        let erases = |from: usize, to: usize| {
+            let mut address = from;
+            let mut sizes = [0; 16];
+            let mut n = 0;
+            while address < to {
+                let erase = largest_erase(&parameters, address, to).unwrap();
+                sizes[n] = erase.size;
+                n += 1;
+                address += erase.size;
+            }
+            (sizes, n)
+        };
+
+        let (sizes, n) = erases(0, 0x11000);
+        assert_eq!(sizes[..n], [65536, 4096]);
+
+        let (sizes, n) = erases(0x6000, 0x20000);
+        assert_eq!(sizes[..n], [4096, 4096, 32768, 65536]);
+
+        // Unaligned start
+        assert_eq!(largest_erase(&parameters, 0x800, 0x2000), None);
+    }
+
+    #[test]
+    fn identify_falls_back_to_jedec_id() {
+        // ISSI, Winbond and GigaDevice tables of revision 1.0
+        let mut parameters = is25lp128();
+        parameters.quad_enable = None;
+        for (id, method) in [
+            ([0x9d, 0x60, 0x18], QuadEnable::Sr1Bit6),
+            ([0xef, 0x40, 0x18], QuadEnable::Sr2Bit1),
+            ([0xc8, 0x40, 0x18], QuadEnable::Sr2Bit1),
+        ] {
+            let identified = identify(id, Ok(parameters)).unwrap();
+            assert_eq!(identified.quad_enable, Some(method));
+        }
+        let identified = identify([0x20, 0xba, 0x18], Ok(parameters)).unwrap();
+        assert_eq!(identified.quad_enable, None);
+
+        // Bogus density
+        parameters.capacity = 0;
+        let identified = identify([0x9d, 0x60, 0x17], Ok(parameters)).unwrap();
+        assert_eq!(identified.capacity, 8 << 20);
+        assert_eq!(identify([0xff, 0xff, 0xff], Ok(parameters)), None);
+
+        assert_eq!(
+            identify([0x9d, 0x60, 0x18], Err(SfdpError::NotPresent)).map(|p| p.capacity),
+            Some(16 << 20)
+        );
+        assert_eq!(
+            identify([0x9d, 0x60, 0x18], Err(SfdpError::FourByteAddressing)),
+            None
+        );
+    }
+}
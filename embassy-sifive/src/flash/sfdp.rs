@@ -0,0 +1,385 @@
+//! JEDEC SFDP (serial flash discoverable parameters) parsing
+//!
+//! The SFDP area starts with a header and a list of parameter headers, each
+//! pointing to a parameter table. Only the basic flash parameter table
+//! (BFPT, JESD216) is used: it describes the density, the erase types, the
+//! fast read commands and, from revision A on, how quad mode is enabled.
+
+use super::{capacity_from_id, cmd, BLOCK_SIZE, MAX_CAPACITY, SECTOR_SIZE};
+
+/// `"SFDP"` read as a little-endian word
+const SIGNATURE: u32 = 0x5044_4653;
+
+/// Parameter ID of the basic flash parameter table
+const BFPT_ID: u16 = 0xff00;
+
+/// Major revision of the supported tables
+const MAJOR_REVISION: u8 = 1;
+
+/// Number of BFPT DWORDs used, up to the quad enable requirements
+const BFPT_DWORDS: usize = 15;
+
+/// SFDP parsing error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SfdpError {
+    /// No SFDP signature
+    NotPresent,
+    /// No basic flash parameter table of a supported revision
+    NoBasicTable,
+    /// The flash only accepts 4-byte addresses
+    FourByteAddressing,
+}
+
+/// Erase command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EraseType {
+    /// Erased size in bytes
+    pub size: usize,
+    /// Command opcode
+    pub opcode: u8,
+}
+
+/// Fast read command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FastRead {
+    /// Command opcode
+    pub opcode: u8,
+    /// Clock cycles between the address and the data, mode bits included
+    pub dummy_cycles: u8,
+}
+
+/// How the quad enable (QE) bit of the flash is set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum QuadEnable {
+    /// Quad commands work without a QE bit
+    NotRequired,
+    /// Bit 6 of status register 1, written with `0x01`
+    Sr1Bit6,
+    /// Bit 7 of status register 2, read with `0x3F` and written with `0x3E`
+    Sr2Bit7,
+    /// Bit 1 of status register 2, read with `0x35`, both status registers
+    /// written with `0x01`
+    Sr2Bit1,
+    /// Bit 1 of status register 2, read with `0x35` and written with `0x31`
+    Sr2Bit1WriteSr2,
+}
+
+/// Flash parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Parameters {
+    /// Size in bytes reachable with 3-byte addresses
+    pub capacity: usize,
+    /// Supported erase commands
+    pub erase_types: [Option<EraseType>; 4],
+    /// Quad enable method, `None` if unknown
+    pub quad_enable: Option<QuadEnable>,
+    /// 1-1-2 fast read
+    pub dual_output: Option<FastRead>,
+    /// 1-2-2 fast read
+    pub dual_io: Option<FastRead>,
+    /// 1-1-4 fast read
+    pub quad_output: Option<FastRead>,
+    /// 1-4-4 fast read
+    pub quad_io: Option<FastRead>,
+}
+
+impl Parameters {
+    /// Parameters of a flash without SFDP, from its JEDEC ID.
+    ///
+    /// Assumes the common 4 KiB sector and 64 KiB block erase commands and
+    /// no dual or quad reads.
+    pub fn from_jedec_id(id: [u8; 3]) -> Option<Self> {
+        Some(Parameters {
+            capacity: capacity_from_id(id)?,
+            erase_types: [
+                Some(EraseType {
+                    size: SECTOR_SIZE,
+                    opcode: cmd::SECTOR_ERASE,
+                }),
+                Some(EraseType {
+                    size: BLOCK_SIZE,
+                    opcode: cmd::BLOCK_ERASE,
+                }),
+                None,
+                None,
+            ],
+            quad_enable: None,
+            dual_output: None,
+            dual_io: None,
+            quad_output: None,
+            quad_io: None,
+        })
+    }
+
+    /// Returns the erase command of `size` bytes
+    pub fn erase_type(&self, size: usize) -> Option<EraseType> {
+        self.erase_types
+            .iter()
+            .flatten()
+            .find(|erase| erase.size == size)
+            .copied()
+    }
+}
+
+fn word(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Reads the SFDP area through `read`, which fills a buffer from an SFDP
+/// address.
+pub(crate) fn parse(mut read: impl FnMut(u32, &mut [u8])) -> Result<Parameters, SfdpError> {
+    let mut header = [0; 8];
+    read(0, &mut header);
+    if word(&header) != SIGNATURE {
+        return Err(SfdpError::NotPresent);
+    }
+
+    // Among the basic tables, later revisions extend earlier ones
+    let mut bfpt = None;
+    for index in 0..=header[6] as u32 {
+        let mut parameter = [0; 8];
+        read(8 + 8 * index, &mut parameter);
+
+        let id = u16::from_le_bytes([parameter[0], parameter[7]]);
+        let (minor, major) = (parameter[1], parameter[2]);
+        if id != BFPT_ID || major != MAJOR_REVISION {
+            continue;
+        }
+
+        let dwords = parameter[3] as usize;
+        let pointer = word(&parameter[4..]) & 0x00ff_ffff;
+        let newer = match bfpt {
+            Some((best, _, _)) => minor >= best,
+            None => true,
+        };
+        if newer {
+            bfpt = Some((minor, pointer, dwords));
+        }
+    }
+    let (_, pointer, dwords) = bfpt.ok_or(SfdpError::NoBasicTable)?;
+
+    let mut table = [0; BFPT_DWORDS * 4];
+    let len = dwords.min(BFPT_DWORDS) * 4;
+    read(pointer, &mut table[..len]);
+
+    let mut dwords = [0; BFPT_DWORDS];
+    for (dword, bytes) in dwords.iter_mut().zip(table[..len].chunks_exact(4)) {
+        *dword = word(bytes);
+    }
+    parse_bfpt(&dwords[..len / 4])
+}
+
+/// Decodes a fast read command from the lower or upper half of a DWORD
+fn fast_read(half: u32) -> FastRead {
+    let dummy = half & 0x1f;
+    let mode = (half >> 5) & 0x7;
+    FastRead {
+        opcode: (half >> 8) as u8,
+        dummy_cycles: (dummy + mode) as u8,
+    }
+}
+
+/// Decodes an erase type from a byte pair of DWORD 8 or 9
+fn erase_type(pair: u32) -> Option<EraseType> {
+    match pair & 0xff {
+        0 => None,
+        exponent => Some(EraseType {
+            size: 1usize.checked_shl(exponent)?,
+            opcode: (pair >> 8) as u8,
+        }),
+    }
+}
+
+/// Decodes the basic flash parameter table, `dwords[0]` being DWORD 1
+fn parse_bfpt(dwords: &[u32]) -> Result<Parameters, SfdpError> {
+    // JESD216 defines 9 DWORDs
+    if dwords.len() < 9 {
+        return Err(SfdpError::NoBasicTable);
+    }
+    let dword = |n: usize| dwords[n - 1];
+
+    let features = dword(1);
+    if (features >> 17) & 0x3 == 0b10 {
+        return Err(SfdpError::FourByteAddressing);
+    }
+
+    let density = dword(2);
+    let bits = if density & (1 << 31) == 0 {
+        density as u64 + 1
+    } else {
+        // An exponent past 63 is not a real density, leave it to the caller
+        // to reject
+        1u64.checked_shl(density & 0x7fff_ffff).unwrap_or(0)
+    };
+    let capacity = (bits / 8).min(MAX_CAPACITY as u64) as usize;
+
+    let mut erase_types = [
+        erase_type(dword(8)),
+        erase_type(dword(8) >> 16),
+        erase_type(dword(9)),
+        erase_type(dword(9) >> 16),
+    ];
+    // Uniform 4 KiB erase, also announced in DWORD 1
+    if features & 0x3 == 0b01 && !erase_types.iter().flatten().any(|e| e.size == SECTOR_SIZE) {
+        if let Some(free) = erase_types.iter_mut().find(|e| e.is_none()) {
+            *free = Some(EraseType {
+                size: SECTOR_SIZE,
+                opcode: (features >> 8) as u8,
+            });
+        }
+    }
+
+    let supported = |bit: u32| features & (1 << bit) != 0;
+    let quad_enable = match dwords.get(14).map(|dword| (dword >> 20) & 0x7) {
+        Some(0b000) => Some(QuadEnable::NotRequired),
+        Some(0b001 | 0b100 | 0b101) => Some(QuadEnable::Sr2Bit1),
+        Some(0b010) => Some(QuadEnable::Sr1Bit6),
+        Some(0b011) => Some(QuadEnable::Sr2Bit7),
+        Some(0b110) => Some(QuadEnable::Sr2Bit1WriteSr2),
+        _ => None,
+    };
+
+    Ok(Parameters {
+        capacity,
+        erase_types,
+        quad_enable,
+        dual_output: supported(16).then(|| fast_read(dword(4))),
+        dual_io: supported(20).then(|| fast_read(dword(4) >> 16)),
+        quad_output: supported(22).then(|| fast_read(dword(3) >> 16)),
+        quad_io: supported(21).then(|| fast_read(dword(3))),
+    })
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::{parse, EraseType, FastRead, QuadEnable, SfdpError};
+
+    /// SFDP area of an ISSI IS25LP128: one BFPT revision 1.6 of 16 DWORDs
+    /// at 0x30
+    pub(crate) fn is25lp128() -> [u8; 0x70] {
+        let mut sfdp = [0xff; 0x70];
+        sfdp[..16].copy_from_slice(&[
+            0x53, 0x46, 0x44, 0x50, 0x06, 0x01, 0x00, 0xff, // header, 1 table
+            0x00, 0x06, 0x01, 0x10, 0x30, 0x00, 0x00, 0xff, // BFPT
+        ]);
+        let bfpt: [u32; 16] = [
+            0xfff9_20e5,
+            0x07ff_ffff,
+            0x6b08_eb44,
+            0xbb04_3b08,
+            0xffff_fffe,
+            0x0000_ffff,
+            0xeb44_ffff,
+            0x520f_200c,
+            0x0000_d810,
+            0x0000_0000,
+            0x0000_0000,
+            0x0000_0000,
+            0x0000_0000,
+            0x0000_0000,
+            0x0023_0000,
+            0x0000_0000,
+        ];
+        for (i, dword) in bfpt.iter().enumerate() {
+            sfdp[0x30 + 4 * i..][..4].copy_from_slice(&dword.to_le_bytes());
+        }
+        sfdp
+    }
+
+    pub(crate) fn reader(sfdp: &[u8]) -> impl FnMut(u32, &mut [u8]) + '_ {
+        |address, buf| {
+            let address = address as usize;
+            buf.copy_from_slice(&sfdp[address..address + buf.len()]);
+        }
+    }
+
+    #[test]
+    fn issi() {
+        let sfdp = is25lp128();
+        let parameters = parse(reader(&sfdp)).unwrap();
+
+        assert_eq!(parameters.capacity, 16 << 20);
+        assert_eq!(
+            parameters.erase_type(4096),
+            Some(EraseType {
+                size: 4096,
+                opcode: 0x20
+            })
+        );
+        assert_eq!(
+            parameters.erase_type(32768),
+            Some(EraseType {
+                size: 32768,
+                opcode: 0x52
+            })
+        );
+        assert_eq!(parameters.erase_type(65536).map(|e| e.opcode), Some(0xd8));
+        assert_eq!(parameters.quad_enable, Some(QuadEnable::Sr1Bit6));
+        assert_eq!(
+            parameters.quad_io,
+            Some(FastRead {
+                opcode: 0xeb,
+                dummy_cycles: 6
+            })
+        );
+        assert_eq!(
+            parameters.quad_output,
+            Some(FastRead {
+                opcode: 0x6b,
+                dummy_cycles: 8
+            })
+        );
+        assert_eq!(
+            parameters.dual_io,
+            Some(FastRead {
+                opcode: 0xbb,
+                dummy_cycles: 4
+            })
+        );
+        assert_eq!(parameters.dual_output.map(|r| r.opcode), Some(0x3b));
+    }
+
+    #[test]
+    fn quad_enable_requirements() {
+        let mut sfdp = is25lp128();
+        for (bits, method) in [
+            (0b000, Some(QuadEnable::NotRequired)),
+            (0b100, Some(QuadEnable::Sr2Bit1)),
+            (0b011, Some(QuadEnable::Sr2Bit7)),
+            (0b110, Some(QuadEnable::Sr2Bit1WriteSr2)),
+            (0b111, None),
+        ] {
+            sfdp[0x30 + 4 * 14 + 2] = bits << 4;
+            assert_eq!(parse(reader(&sfdp)).unwrap().quad_enable, method);
+        }
+    }
+
+    #[test]
+    fn jesd216_table() {
+        // Original revision: 9 DWORDs, no quad enable requirements
+        let mut sfdp = is25lp128();
+        sfdp[0x0b] = 9;
+        let parameters = parse(reader(&sfdp)).unwrap();
+        assert_eq!(parameters.quad_enable, None);
+        assert_eq!(parameters.capacity, 16 << 20);
+    }
+
+    #[test]
+    fn large_density() {
+        // 2^34 bits, only the first 16 MiB are reachable
+        let mut sfdp = is25lp128();
+        sfdp[0x34..0x38].copy_from_slice(&0x8000_0022u32.to_le_bytes());
+        assert_eq!(parse(reader(&sfdp)).unwrap().capacity, 16 << 20);
+    }
+
+    #[test]
+    fn missing_sfdp() {
+        let sfdp = [0xff; 16];
+        assert_eq!(parse(reader(&sfdp)), Err(SfdpError::NotPresent));
+    }
+}
@@ -2,7 +2,10 @@
 
 use core::marker::PhantomData;
 
-use portable_atomic::{AtomicU32, Ordering};
+use portable_atomic::{AtomicPtr, AtomicU32, Ordering};
+
+use crate::interrupt;
+use crate::interrupt::typelevel::{GpioInterrupt, Handler};
 
 /// GpioExt trait extends the GPIO0 peripheral.
 pub trait GpioExt {
@@ -57,6 +60,54 @@ pub struct NoInvert;
 /// Invert output mode (type state)
 pub struct Invert;
 
+/// Edge that triggers a GPIO interrupt
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Edge {
+    /// Low to high transition
+    Rising,
+    /// High to low transition
+    Falling,
+    /// Any transition
+    Both,
+}
+
+/// Input pins that can raise edge interrupts
+pub trait InterruptPin: embedded_hal::digital::InputPin + IntoUnknown {
+    /// PLIC source of the pin
+    type Interrupt: GpioInterrupt;
+
+    /// Returns the GPIO index of the pin
+    fn index(&self) -> usize;
+
+    /// Enables the interrupt for the given edge, disabling any other edge
+    fn enable_interrupt(&mut self, edge: Edge);
+
+    /// Disables all edge interrupts of the pin
+    fn disable_interrupt(&mut self);
+
+    /// Clears the pending edge interrupts of the pin
+    fn clear_interrupt(&mut self);
+}
+
+/// Pins that can be returned to their reset state
+pub trait IntoUnknown {
+    /// The pin in [`Unknown`] mode
+    type Unknown;
+
+    /// Clears every per-pin configuration bit, leaving the pin as after reset:
+    /// IOF, input, output, pull-up, high drive, output inversion and all
+    /// interrupts are disabled, so the pin is high-impedance.
+    fn into_unknown(self) -> Self::Unknown;
+
+    /// Clears the configuration like [`IntoUnknown::into_unknown`], but in
+    /// place.
+    ///
+    /// The type state no longer matches the hardware afterwards, so this is
+    /// meant for drivers that release their pins when dropped.
+    fn reset(&mut self);
+}
+
 trait PinIndex {
     const INDEX: usize;
 }
@@ -70,88 +121,259 @@ fn atomic_set_bit(r: &AtomicU32, index: usize, bit: bool) {
     };
 }
 
+/// GPIO0 registers used by the pin drivers
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Register {
+    /// Pin value
+    InputVal,
+    /// Pin input enable
+    InputEn,
+    /// Pin output enable
+    OutputEn,
+    /// Output value
+    OutputVal,
+    /// Internal pull-up enable
+    Pullup,
+    /// Pin drive strength
+    Drive,
+    /// Rise interrupt enable
+    RiseIe,
+    /// Rise interrupt pending
+    RiseIp,
+    /// Fall interrupt enable
+    FallIe,
+    /// Fall interrupt pending
+    FallIp,
+    /// High interrupt enable
+    HighIe,
+    /// Low interrupt enable
+    LowIe,
+    /// IOF enable
+    IofEn,
+    /// IOF select
+    IofSel,
+    /// Output XOR (invert)
+    OutXor,
+}
+
+/// Returns the GPIO0 register `reg` as an atomic word
+#[cfg(not(any(test, feature = "mock")))]
+fn register(reg: Register) -> &'static AtomicU32 {
+    let p = unsafe { &*e310x::Gpio0::ptr() };
+    unsafe {
+        match reg {
+            Register::InputVal => core::mem::transmute(p.input_val()),
+            Register::InputEn => core::mem::transmute(p.input_en()),
+            Register::OutputEn => core::mem::transmute(p.output_en()),
+            Register::OutputVal => core::mem::transmute(p.output_val()),
+            Register::Pullup => core::mem::transmute(p.pullup()),
+            Register::Drive => core::mem::transmute(p.drive()),
+            Register::RiseIe => core::mem::transmute(p.rise_ie()),
+            Register::RiseIp => core::mem::transmute(p.rise_ip()),
+            Register::FallIe => core::mem::transmute(p.fall_ie()),
+            Register::FallIp => core::mem::transmute(p.fall_ip()),
+            Register::HighIe => core::mem::transmute(p.high_ie()),
+            Register::LowIe => core::mem::transmute(p.low_ie()),
+            Register::IofEn => core::mem::transmute(p.iof_en()),
+            Register::IofSel => core::mem::transmute(p.iof_sel()),
+            Register::OutXor => core::mem::transmute(p.out_xor()),
+        }
+    }
+}
+
+/// Clears the `mask` bits of a write-1-to-clear pending register
+#[cfg(not(any(test, feature = "mock")))]
+fn clear_pending(reg: Register, mask: u32) {
+    register(reg).store(mask, Ordering::SeqCst);
+}
+
+#[cfg(any(test, feature = "mock"))]
+use mock::{clear_pending, register};
+
+/// In-memory model of the GPIO0 register block.
+///
+/// With the `mock` feature (or in this crate's unit tests) the pin drivers
+/// access this model instead of the hardware, so the typestate logic can be
+/// checked on the host. The model is shared by all threads, so concurrent
+/// tests should use distinct pins.
+#[cfg(any(test, feature = "mock"))]
+pub mod mock {
+    use portable_atomic::{AtomicU32, Ordering};
+
+    use super::Register;
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: AtomicU32 = AtomicU32::new(0);
+    static REGISTERS: [AtomicU32; 15] = [ZERO; 15];
+
+    pub(super) fn register(reg: Register) -> &'static AtomicU32 {
+        &REGISTERS[reg as usize]
+    }
+
+    pub(super) fn clear_pending(reg: Register, mask: u32) {
+        register(reg).fetch_and(!mask, Ordering::SeqCst);
+    }
+
+    /// Returns the value of a register
+    pub fn read(reg: Register) -> u32 {
+        register(reg).load(Ordering::SeqCst)
+    }
+
+    /// Returns the bit of pin `index` in a register
+    pub fn bit(reg: Register, index: usize) -> bool {
+        (read(reg) >> (index & 31)) & 1 != 0
+    }
+
+    /// Sets the bit of pin `index` in a register, e.g. to drive an input level
+    /// or latch a pending interrupt
+    pub fn set_bit(reg: Register, index: usize, bit: bool) {
+        super::atomic_set_bit(register(reg), index, bit);
+    }
+}
+
 trait PeripheralAccess {
-    fn peripheral() -> e310x::Gpio0;
+    fn register(reg: Register) -> &'static AtomicU32;
 
     fn input_value(index: usize) -> bool {
-        let p = Self::peripheral();
-        (p.input_val().read().bits() >> (index & 31) & 1) != 0
+        (Self::register(Register::InputVal).load(Ordering::SeqCst) >> (index & 31) & 1) != 0
     }
 
     fn set_input_en(index: usize, bit: bool) {
-        let p = Self::peripheral();
-        let r: &AtomicU32 = unsafe { core::mem::transmute(p.input_en()) };
-        atomic_set_bit(r, index, bit);
+        atomic_set_bit(Self::register(Register::InputEn), index, bit);
     }
 
     fn set_output_en(index: usize, bit: bool) {
-        let p = Self::peripheral();
-        let r: &AtomicU32 = unsafe { core::mem::transmute(p.output_en()) };
-        atomic_set_bit(r, index, bit);
+        atomic_set_bit(Self::register(Register::OutputEn), index, bit);
     }
 
     fn output_value(index: usize) -> bool {
-        let p = Self::peripheral();
-        ((p.output_val().read().bits() >> (index & 31)) & 1) != 0
+        ((Self::register(Register::OutputVal).load(Ordering::SeqCst) >> (index & 31)) & 1) != 0
     }
 
     fn set_output_value(index: usize, bit: bool) {
-        let p = Self::peripheral();
-        let r: &AtomicU32 = unsafe { core::mem::transmute(p.output_val()) };
-        atomic_set_bit(r, index, bit);
+        atomic_set_bit(Self::register(Register::OutputVal), index, bit);
     }
 
     fn toggle_pin(index: usize) {
-        let p = Self::peripheral();
-        let r: &AtomicU32 = unsafe { core::mem::transmute(p.output_val()) };
+        let r = Self::register(Register::OutputVal);
         let mask = 1 << (index & 31);
         r.fetch_xor(mask, Ordering::SeqCst);
     }
 
     fn set_pullup(index: usize, bit: bool) {
-        let p = Self::peripheral();
-        let r: &AtomicU32 = unsafe { core::mem::transmute(p.pullup()) };
-        atomic_set_bit(r, index, bit);
+        atomic_set_bit(Self::register(Register::Pullup), index, bit);
     }
 
     fn set_drive(index: usize, bit: bool) {
-        let p = Self::peripheral();
-        let r: &AtomicU32 = unsafe { core::mem::transmute(p.drive()) };
-        atomic_set_bit(r, index, bit);
+        atomic_set_bit(Self::register(Register::Drive), index, bit);
     }
 
     fn set_out_xor(index: usize, bit: bool) {
-        let p = Self::peripheral();
-        let r: &AtomicU32 = unsafe { core::mem::transmute(p.out_xor()) };
-        atomic_set_bit(r, index, bit);
+        atomic_set_bit(Self::register(Register::OutXor), index, bit);
     }
 
     fn set_iof_en(index: usize, bit: bool) {
-        let p = Self::peripheral();
-        let r: &AtomicU32 = unsafe { core::mem::transmute(p.iof_en()) };
-        atomic_set_bit(r, index, bit);
+        atomic_set_bit(Self::register(Register::IofEn), index, bit);
     }
 
     fn set_iof_sel(index: usize, bit: bool) {
-        let p = Self::peripheral();
-        let r: &AtomicU32 = unsafe { core::mem::transmute(p.iof_sel()) };
-        atomic_set_bit(r, index, bit);
+        atomic_set_bit(Self::register(Register::IofSel), index, bit);
+    }
+
+    fn set_rise_ie(index: usize, bit: bool) {
+        atomic_set_bit(Self::register(Register::RiseIe), index, bit);
+    }
+
+    fn set_fall_ie(index: usize, bit: bool) {
+        atomic_set_bit(Self::register(Register::FallIe), index, bit);
+    }
+
+    fn set_high_ie(index: usize, bit: bool) {
+        atomic_set_bit(Self::register(Register::HighIe), index, bit);
+    }
+
+    fn set_low_ie(index: usize, bit: bool) {
+        atomic_set_bit(Self::register(Register::LowIe), index, bit);
+    }
+
+    fn clear_edge_ip(index: usize) {
+        let mask = 1 << (index & 31);
+        clear_pending(Register::RiseIp, mask);
+        clear_pending(Register::FallIp, mask);
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_HANDLER: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+/// Handlers called from the GPIO interrupts, one per pin
+static HANDLERS: [AtomicPtr<()>; 32] = [NO_HANDLER; 32];
+
+/// Installs `handler` for the edge interrupt of pin `I::PIN` and enables its PLIC source.
+///
+/// The handler runs in interrupt context after the pending bits have been cleared.
+/// It receives the pin index and the edge that fired, or [`Edge::Both`] if both
+/// edges were pending.
+pub(crate) fn set_interrupt_handler<I: GpioInterrupt>(handler: fn(usize, Edge)) {
+    HANDLERS[I::PIN].store(handler as *mut (), Ordering::SeqCst);
+    interrupt::enable::<I>();
+}
+
+/// Removes the edge interrupt handler of pin `I::PIN` and disables its PLIC source
+pub(crate) fn clear_interrupt_handler<I: GpioInterrupt>() {
+    I::disable();
+    HANDLERS[I::PIN].store(core::ptr::null_mut(), Ordering::SeqCst);
+}
+
+/// GPIO edge interrupt handler, dispatching to the driver that owns the pin.
+///
+/// Bind it to the `GPIOn` sources of the pins used by [`crate::counter`] or
+/// [`crate::capture`] with [`crate::bind_interrupts!`].
+pub struct InterruptHandler;
+
+impl<I: GpioInterrupt> Handler<I> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        on_interrupt(I::PIN);
+    }
+}
+
+fn on_interrupt(index: usize) {
+    let read = |reg| register(reg).load(Ordering::SeqCst);
+    // Pending bits latch regardless of the enables, so mask them
+    let rise = read(Register::RiseIp) & read(Register::RiseIe);
+    let fall = read(Register::FallIp) & read(Register::FallIe);
+    let (rise, fall) = ((rise >> index) & 1 != 0, (fall >> index) & 1 != 0);
+    <e310x::Gpio0 as PeripheralAccess>::clear_edge_ip(index);
+
+    let edge = match (rise, fall) {
+        (true, false) => Edge::Rising,
+        (false, true) => Edge::Falling,
+        (true, true) => Edge::Both,
+        (false, false) => return,
+    };
+
+    let handler = HANDLERS[index].load(Ordering::SeqCst);
+    if !handler.is_null() {
+        let handler: fn(usize, Edge) = unsafe { core::mem::transmute(handler) };
+        handler(index, edge);
     }
 }
 
 macro_rules! gpio {
     ($GPIOX:ident, $gpiox:ident, [
-        $($PXi:ident: ($pxi:ident, $i:expr, $MODE:ty),)+
+        $($PXi:ident: ($pxi:ident, $i:expr, $MODE:ty, $GPIOi:ident),)+
     ]) => {
         /// GPIO
         pub mod $gpiox {
             use core::marker::PhantomData;
             use core::convert::Infallible;
 
+            use portable_atomic::AtomicU32;
             use embedded_hal::digital::{InputPin, OutputPin, StatefulOutputPin, ErrorType};
             use e310x::$GPIOX;
-            use super::{Unknown, IOF0, IOF1, Drive, Floating, GpioExt, Input, Invert,
-                        NoInvert, Output, PullUp, Regular, PinIndex, PeripheralAccess};
+            use crate::interrupt::typelevel;
+            use super::{Unknown, IOF0, IOF1, Drive, Edge, Floating, GpioExt, Input, InterruptPin,
+                        IntoUnknown, Invert, NoInvert, Output, PullUp, Regular, PinIndex,
+                        PeripheralAccess, Register};
 
             /// GPIO parts for fine grained permission control.
             pub struct Parts {
@@ -163,8 +385,8 @@ macro_rules! gpio {
 
             impl PeripheralAccess for $GPIOX {
                 #[inline(always)]
-                fn peripheral() -> e310x::Gpio0 {
-                    unsafe { $GPIOX::steal() }
+                fn register(reg: Register) -> &'static AtomicU32 {
+                    super::register(reg)
                 }
             }
 
@@ -191,6 +413,30 @@ macro_rules! gpio {
                 }
 
                 impl<MODE> $PXi<MODE> {
+                    /// Returns the pin to its reset state, see [`IntoUnknown`]
+                    pub fn into_unknown(mut self) -> $PXi<Unknown> {
+                        self.reset();
+                        $PXi { _mode: PhantomData }
+                    }
+
+                    /// Clears every per-pin configuration bit in place, see
+                    /// [`IntoUnknown::reset`]
+                    pub fn reset(&mut self) {
+                        $GPIOX::set_iof_en(Self::INDEX, false);
+                        $GPIOX::set_iof_sel(Self::INDEX, false);
+                        $GPIOX::set_output_en(Self::INDEX, false);
+                        $GPIOX::set_output_value(Self::INDEX, false);
+                        $GPIOX::set_input_en(Self::INDEX, false);
+                        $GPIOX::set_pullup(Self::INDEX, false);
+                        $GPIOX::set_drive(Self::INDEX, false);
+                        $GPIOX::set_out_xor(Self::INDEX, false);
+                        $GPIOX::set_rise_ie(Self::INDEX, false);
+                        $GPIOX::set_fall_ie(Self::INDEX, false);
+                        $GPIOX::set_high_ie(Self::INDEX, false);
+                        $GPIOX::set_low_ie(Self::INDEX, false);
+                        $GPIOX::clear_edge_ip(Self::INDEX);
+                    }
+
                     /// Configures the pin to serve as alternate function 0 (AF0)
                     pub fn into_iof0(self) -> $PXi<IOF0<NoInvert>> {
                         $GPIOX::set_out_xor(Self::INDEX, false);
@@ -278,6 +524,20 @@ macro_rules! gpio {
                     }
                 }
 
+                impl<MODE> IntoUnknown for $PXi<MODE> {
+                    type Unknown = $PXi<Unknown>;
+
+                    #[inline]
+                    fn into_unknown(self) -> $PXi<Unknown> {
+                        $PXi::into_unknown(self)
+                    }
+
+                    #[inline]
+                    fn reset(&mut self) {
+                        $PXi::reset(self)
+                    }
+                }
+
                 impl<MODE> ErrorType for $PXi<Input<MODE>> {
                     type Error = Infallible;
                 }
@@ -298,6 +558,37 @@ macro_rules! gpio {
                     }
                 }
 
+                impl<MODE> InterruptPin for $PXi<Input<MODE>> {
+                    type Interrupt = typelevel::$GPIOi;
+
+                    #[inline]
+                    fn index(&self) -> usize {
+                        Self::INDEX
+                    }
+
+                    fn enable_interrupt(&mut self, edge: Edge) {
+                        let (rise, fall) = match edge {
+                            Edge::Rising => (true, false),
+                            Edge::Falling => (false, true),
+                            Edge::Both => (true, true),
+                        };
+                        $GPIOX::clear_edge_ip(Self::INDEX);
+                        $GPIOX::set_rise_ie(Self::INDEX, rise);
+                        $GPIOX::set_fall_ie(Self::INDEX, fall);
+                    }
+
+                    fn disable_interrupt(&mut self) {
+                        $GPIOX::set_rise_ie(Self::INDEX, false);
+                        $GPIOX::set_fall_ie(Self::INDEX, false);
+                        $GPIOX::clear_edge_ip(Self::INDEX);
+                    }
+
+                    #[inline]
+                    fn clear_interrupt(&mut self) {
+                        $GPIOX::clear_edge_ip(Self::INDEX);
+                    }
+                }
+
                 impl<MODE> OutputPin for $PXi<Output<MODE>> {
                     #[inline]
                     fn set_high(&mut self) -> Result<(), Infallible> {
@@ -338,36 +629,163 @@ macro_rules! gpio {
 // * bootloader may reconfigure some GPIOs
 // * we do not enforce any specific state in `split()`
 gpio!(Gpio0, gpio0, [
-    Pin0: (pin0, 0, Unknown),
-    Pin1: (pin1, 1, Unknown),
-    Pin2: (pin2, 2, Unknown),
-    Pin3: (pin3, 3, Unknown),
-    Pin4: (pin4, 4, Unknown),
-    Pin5: (pin5, 5, Unknown),
-    Pin6: (pin6, 6, Unknown),
-    Pin7: (pin7, 7, Unknown),
-    Pin8: (pin8, 8, Unknown),
-    Pin9: (pin9, 9, Unknown),
-    Pin10: (pin10, 10, Unknown),
-    Pin11: (pin11, 11, Unknown),
-    Pin12: (pin12, 12, Unknown),
-    Pin13: (pin13, 13, Unknown),
-    Pin14: (pin14, 14, Unknown),
-    Pin15: (pin15, 15, Unknown),
-    Pin16: (pin16, 16, Unknown),
-    Pin17: (pin17, 17, Unknown),
-    Pin18: (pin18, 18, Unknown),
-    Pin19: (pin19, 19, Unknown),
-    Pin20: (pin20, 20, Unknown),
-    Pin21: (pin21, 21, Unknown),
-    Pin22: (pin22, 22, Unknown),
-    Pin23: (pin23, 23, Unknown),
-    Pin24: (pin24, 24, Unknown),
-    Pin25: (pin25, 25, Unknown),
-    Pin26: (pin26, 26, Unknown),
-    Pin27: (pin27, 27, Unknown),
-    Pin28: (pin28, 28, Unknown),
-    Pin29: (pin29, 29, Unknown),
-    Pin30: (pin30, 30, Unknown),
-    Pin31: (pin31, 31, Unknown),
+    Pin0: (pin0, 0, Unknown, GPIO0),
+    Pin1: (pin1, 1, Unknown, GPIO1),
+    Pin2: (pin2, 2, Unknown, GPIO2),
+    Pin3: (pin3, 3, Unknown, GPIO3),
+    Pin4: (pin4, 4, Unknown, GPIO4),
+    Pin5: (pin5, 5, Unknown, GPIO5),
+    Pin6: (pin6, 6, Unknown, GPIO6),
+    Pin7: (pin7, 7, Unknown, GPIO7),
+    Pin8: (pin8, 8, Unknown, GPIO8),
+    Pin9: (pin9, 9, Unknown, GPIO9),
+    Pin10: (pin10, 10, Unknown, GPIO10),
+    Pin11: (pin11, 11, Unknown, GPIO11),
+    Pin12: (pin12, 12, Unknown, GPIO12),
+    Pin13: (pin13, 13, Unknown, GPIO13),
+    Pin14: (pin14, 14, Unknown, GPIO14),
+    Pin15: (pin15, 15, Unknown, GPIO15),
+    Pin16: (pin16, 16, Unknown, GPIO16),
+    Pin17: (pin17, 17, Unknown, GPIO17),
+    Pin18: (pin18, 18, Unknown, GPIO18),
+    Pin19: (pin19, 19, Unknown, GPIO19),
+    Pin20: (pin20, 20, Unknown, GPIO20),
+    Pin21: (pin21, 21, Unknown, GPIO21),
+    Pin22: (pin22, 22, Unknown, GPIO22),
+    Pin23: (pin23, 23, Unknown, GPIO23),
+    Pin24: (pin24, 24, Unknown, GPIO24),
+    Pin25: (pin25, 25, Unknown, GPIO25),
+    Pin26: (pin26, 26, Unknown, GPIO26),
+    Pin27: (pin27, 27, Unknown, GPIO27),
+    Pin28: (pin28, 28, Unknown, GPIO28),
+    Pin29: (pin29, 29, Unknown, GPIO29),
+    Pin30: (pin30, 30, Unknown, GPIO30),
+    Pin31: (pin31, 31, Unknown, GPIO31),
 ]);
+
+#[cfg(test)]
+mod tests {
+    use super::gpio0::Parts;
+    use super::mock::{bit, set_bit};
+    use super::{Edge, GpioExt, InterruptPin, Register};
+    use embedded_hal::digital::{InputPin, StatefulOutputPin};
+
+    fn pins() -> Parts {
+        unsafe { e310x::Gpio0::steal() }.split()
+    }
+
+    #[test]
+    fn into_output() {
+        let _pin = pins().pin0.into_iof0().into_output();
+
+        assert!(bit(Register::OutputEn, 0));
+        assert!(!bit(Register::IofEn, 0));
+        assert!(!bit(Register::Drive, 0));
+        assert!(!bit(Register::OutXor, 0));
+    }
+
+    #[test]
+    fn into_inverted_output_drive() {
+        let _pin = pins().pin1.into_inverted_output_drive();
+
+        assert!(bit(Register::OutputEn, 1));
+        assert!(bit(Register::Drive, 1));
+        assert!(bit(Register::OutXor, 1));
+    }
+
+    #[test]
+    fn into_iof() {
+        let _pin = pins().pin2.into_iof1();
+        assert!(bit(Register::IofEn, 2));
+        assert!(bit(Register::IofSel, 2));
+        assert!(!bit(Register::OutXor, 2));
+
+        let _pin = pins().pin2.into_inverted_iof0();
+        assert!(bit(Register::IofEn, 2));
+        assert!(!bit(Register::IofSel, 2));
+        assert!(bit(Register::OutXor, 2));
+    }
+
+    #[test]
+    fn toggle() {
+        let mut pin = pins().pin3.into_output();
+
+        assert!(pin.is_set_low().unwrap());
+        pin.toggle().unwrap();
+        assert!(bit(Register::OutputVal, 3));
+        assert!(pin.is_set_high().unwrap());
+        pin.toggle().unwrap();
+        assert!(!bit(Register::OutputVal, 3));
+    }
+
+    #[test]
+    fn pull_up_input() {
+        let mut pin = pins().pin4.into_iof0().into_pull_up_input();
+
+        assert!(bit(Register::Pullup, 4));
+        assert!(bit(Register::InputEn, 4));
+        assert!(!bit(Register::IofEn, 4));
+
+        set_bit(Register::InputVal, 4, true);
+        assert!(pin.is_high().unwrap());
+        set_bit(Register::InputVal, 4, false);
+        assert!(pin.is_low().unwrap());
+    }
+
+    #[test]
+    fn edge_interrupts() {
+        let mut pin = pins().pin5.into_floating_input();
+
+        pin.enable_interrupt(Edge::Both);
+        assert!(bit(Register::RiseIe, 5));
+        assert!(bit(Register::FallIe, 5));
+
+        pin.enable_interrupt(Edge::Falling);
+        assert!(!bit(Register::RiseIe, 5));
+        assert!(bit(Register::FallIe, 5));
+
+        set_bit(Register::FallIp, 5, true);
+        pin.clear_interrupt();
+        assert!(!bit(Register::FallIp, 5));
+    }
+
+    #[test]
+    fn into_unknown() {
+        let mut pin = pins().pin6.into_pull_up_input();
+        pin.enable_interrupt(Edge::Rising);
+        let _pin = pin.into_output_drive().into_inverted_iof1().into_unknown();
+
+        for reg in [
+            Register::InputEn,
+            Register::OutputEn,
+            Register::OutputVal,
+            Register::Pullup,
+            Register::Drive,
+            Register::RiseIe,
+            Register::FallIe,
+            Register::IofEn,
+            Register::IofSel,
+            Register::OutXor,
+        ] {
+            assert!(!bit(reg, 6), "{:?} still set", reg);
+        }
+    }
+
+    #[test]
+    fn reset_in_place() {
+        let mut pin = pins().pin7.into_pull_up_input();
+        pin.enable_interrupt(Edge::Both);
+        set_bit(Register::RiseIp, 7, true);
+        pin.reset();
+
+        for reg in [
+            Register::InputEn,
+            Register::Pullup,
+            Register::RiseIe,
+            Register::FallIe,
+            Register::RiseIp,
+        ] {
+            assert!(!bit(reg, 7), "{:?} still set", reg);
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! PLIC interrupt bindings
+//!
+//! The HAL does not define any external interrupt handler itself. Instead the
+//! application binds drivers to PLIC sources with [`bind_interrupts!`], which
+//! generates the handler symbol and a proof type that is passed to the
+//! driver constructors:
+//!
+//! ```ignore
+//! embassy_sifive::bind_interrupts!(struct Irqs {
+//!     UART0 => uart::InterruptHandler<Uart0>;
+//!     GPIO9 => gpio::InterruptHandler, MyGpio9Handler;
+//! });
+//!
+//! let serial = Serial::new_async(uart0, (tx, rx), Irqs, Config::default(), clocks)?;
+//! ```
+//!
+//! Several handlers can be chained on one source, they run in the order they
+//! are listed.
+//!
+//! # PLIC management
+//!
+//! The functions of this module only ever touch the source they are given,
+//! so configuring one driver cannot disable the interrupts of another. The
+//! priority chosen for each source is kept in a table: drivers apply it when
+//! they enable their source, falling back to [`DEFAULT_PRIORITY`] for
+//! sources the application did not configure.
+
+use e310x::interrupt::{ExternalInterrupt, Priority};
+use e310x::PLIC;
+use portable_atomic::{AtomicU8, Ordering};
+use riscv::{InterruptNumber, PriorityNumber};
+
+/// Priority given to a source enabled by a driver if the application did not
+/// choose one. Sources with priority 0 never fire.
+pub const DEFAULT_PRIORITY: Priority = Priority::P1;
+
+/// Type-level interrupt infrastructure
+pub mod typelevel {
+    use e310x::interrupt::{ExternalInterrupt, Priority};
+    use e310x::PLIC;
+
+    mod sealed {
+        pub trait Interrupt {}
+    }
+
+    /// Type-level PLIC source
+    pub trait Interrupt: sealed::Interrupt + 'static {
+        /// The PLIC source
+        const IRQ: ExternalInterrupt;
+
+        /// Enables the source in the PLIC context of the hart, see [`super::enable_source`]
+        ///
+        /// # Safety
+        ///
+        /// The handlers bound to the source may run as soon as this returns.
+        #[inline]
+        unsafe fn enable() {
+            super::enable_source(Self::IRQ);
+        }
+
+        /// Disables the source
+        #[inline]
+        fn disable() {
+            super::disable_source(Self::IRQ);
+        }
+
+        /// Returns `true` if the source is enabled
+        #[inline]
+        fn is_enabled() -> bool {
+            PLIC::ctx0().enables().is_enabled(Self::IRQ)
+        }
+
+        /// Returns `true` if the source is pending
+        #[inline]
+        fn is_pending() -> bool {
+            PLIC::pendings().is_pending(Self::IRQ)
+        }
+
+        /// Returns the priority of the source
+        #[inline]
+        fn priority() -> Priority {
+            PLIC::priorities().get_priority(Self::IRQ)
+        }
+
+        /// Sets the priority of the source, see [`super::set_priority`]
+        ///
+        /// # Safety
+        ///
+        /// Changing priorities can break priority-based critical sections.
+        #[inline]
+        unsafe fn set_priority(priority: Priority) {
+            super::set_priority(Self::IRQ, priority);
+        }
+    }
+
+    /// GPIO edge interrupt source
+    pub trait GpioInterrupt: Interrupt {
+        /// Index of the pin
+        const PIN: usize;
+    }
+
+    /// Interrupt handler trait.
+    ///
+    /// Drivers that need to handle interrupts implement this trait.
+    /// The user must ensure `on_interrupt()` is called every time the interrupt fires.
+    /// Drivers must use [`Binding`] to assert at compile time that the user has done so.
+    pub trait Handler<I: Interrupt> {
+        /// Interrupt handler function.
+        ///
+        /// Must be called every time the `I` interrupt fires, synchronously from
+        /// the interrupt handler context.
+        ///
+        /// # Safety
+        ///
+        /// This function must ONLY be called from the interrupt handler for `I`.
+        unsafe fn on_interrupt();
+    }
+
+    /// Compile-time assertion that an interrupt has been bound to a handler.
+    ///
+    /// For the vast majority of cases, you should use the `bind_interrupts!`
+    /// macro instead of writing `unsafe impl`s of this trait.
+    ///
+    /// # Safety
+    ///
+    /// By implementing this trait, you are asserting that you have arranged for `H::on_interrupt()`
+    /// to be called every time the `I` interrupt fires.
+    ///
+    /// This allows drivers to check bindings at compile-time.
+    pub unsafe trait Binding<I: Interrupt, H: Handler<I>> {}
+
+    macro_rules! interrupts {
+        ($($irq:ident,)+) => {
+            $(
+                #[allow(non_camel_case_types)]
+                #[doc = concat!(stringify!($irq), " PLIC source")]
+                pub enum $irq {}
+
+                impl sealed::Interrupt for $irq {}
+                impl Interrupt for $irq {
+                    const IRQ: ExternalInterrupt = ExternalInterrupt::$irq;
+                }
+            )+
+        };
+    }
+
+    macro_rules! gpio_interrupts {
+        ($($irq:ident: $i:expr,)+) => {
+            interrupts!($($irq,)+);
+
+            $(
+                impl GpioInterrupt for $irq {
+                    const PIN: usize = $i;
+                }
+            )+
+        };
+    }
+
+    interrupts!(UART0, QSPI0, QSPI1, QSPI2,);
+
+    #[cfg(feature = "g002")]
+    interrupts!(UART1, I2C0,);
+
+    gpio_interrupts!(
+        GPIO0: 0,
+        GPIO1: 1,
+        GPIO2: 2,
+        GPIO3: 3,
+        GPIO4: 4,
+        GPIO5: 5,
+        GPIO6: 6,
+        GPIO7: 7,
+        GPIO8: 8,
+        GPIO9: 9,
+        GPIO10: 10,
+        GPIO11: 11,
+        GPIO12: 12,
+        GPIO13: 13,
+        GPIO14: 14,
+        GPIO15: 15,
+        GPIO16: 16,
+        GPIO17: 17,
+        GPIO18: 18,
+        GPIO19: 19,
+        GPIO20: 20,
+        GPIO21: 21,
+        GPIO22: 22,
+        GPIO23: 23,
+        GPIO24: 24,
+        GPIO25: 25,
+        GPIO26: 26,
+        GPIO27: 27,
+        GPIO28: 28,
+        GPIO29: 29,
+        GPIO30: 30,
+        GPIO31: 31,
+    );
+}
+
+/// Number of PLIC sources, source 0 is reserved
+const SOURCES: usize = ExternalInterrupt::MAX_INTERRUPT_NUMBER + 1;
+
+/// Marks a source whose priority was never set, distinct from an explicit P0
+const UNSET: u8 = u8::MAX;
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_PRIORITY: AtomicU8 = AtomicU8::new(UNSET);
+/// Priority chosen for each source, [`UNSET`] if the application never set one
+static PRIORITIES: [AtomicU8; SOURCES] = [NO_PRIORITY; SOURCES];
+
+/// Sets the priority of `source` and records it in the priority table.
+///
+/// Priority 0 masks the source; it stays enabled but never fires.
+///
+/// # Safety
+///
+/// Changing priorities can break priority-based critical sections.
+pub unsafe fn set_priority(source: ExternalInterrupt, priority: Priority) {
+    PRIORITIES[source.number()].store(priority.number() as u8, Ordering::Relaxed);
+    PLIC::priorities().set_priority(source, priority);
+}
+
+/// Returns the priority recorded for `source`, or [`DEFAULT_PRIORITY`] if
+/// none was set
+pub fn priority(source: ExternalInterrupt) -> Priority {
+    match PRIORITIES[source.number()].load(Ordering::Relaxed) {
+        UNSET => DEFAULT_PRIORITY,
+        number => Priority::from_number(number as usize).unwrap_or(DEFAULT_PRIORITY),
+    }
+}
+
+/// Writes the priority table back to the PLIC.
+///
+/// Restores the configured priorities after code outside the HAL reset them.
+pub fn restore_priorities() {
+    for number in 1..SOURCES {
+        // UNSET is not a valid priority number and is skipped
+        let priority = PRIORITIES[number].load(Ordering::Relaxed);
+        if let (Ok(source), Ok(priority)) = (
+            ExternalInterrupt::from_number(number),
+            Priority::from_number(priority as usize),
+        ) {
+            unsafe { PLIC::priorities().set_priority(source, priority) };
+        }
+    }
+}
+
+/// Applies the recorded priority of `source` and enables it in the PLIC
+/// context of the hart, together with machine external interrupts.
+///
+/// # Safety
+///
+/// The handlers bound to `source` may run as soon as this returns.
+pub unsafe fn enable_source(source: ExternalInterrupt) {
+    PLIC::priorities().set_priority(source, priority(source));
+    PLIC::ctx0().enables().enable(source);
+    PLIC::enable();
+    riscv::interrupt::enable();
+}
+
+/// Disables `source`, keeping its recorded priority
+pub fn disable_source(source: ExternalInterrupt) {
+    PLIC::ctx0().enables().disable(source);
+}
+
+/// Returns the priority threshold of the hart context
+pub fn threshold() -> Priority {
+    PLIC::ctx0().threshold().get_threshold()
+}
+
+/// Sets the priority threshold of the hart context and returns the previous one.
+///
+/// Only sources with a priority above the threshold interrupt the hart.
+///
+/// # Safety
+///
+/// Lowering the threshold can break priority-based critical sections.
+pub unsafe fn set_threshold(threshold: Priority) -> Priority {
+    let ctx = PLIC::ctx0();
+    let previous = ctx.threshold().get_threshold();
+    ctx.threshold().set_threshold(threshold);
+    previous
+}
+
+/// Runs `f` with every source at or below `threshold` masked.
+///
+/// The threshold is only ever raised, and the previous one is restored
+/// afterwards, so nested calls compose.
+pub fn with_threshold<R>(threshold: Priority, f: impl FnOnce() -> R) -> R {
+    let previous = self::threshold();
+    if threshold.number() <= previous.number() {
+        return f();
+    }
+
+    unsafe { set_threshold(threshold) };
+    let result = f();
+    unsafe { set_threshold(previous) };
+    result
+}
+
+/// Enables a PLIC source on behalf of a driver, see [`enable_source`]
+pub(crate) fn enable<I: typelevel::Interrupt>() {
+    unsafe { enable_source(I::IRQ) };
+}
+
+/// Defines the PLIC handlers of the application and binds drivers to them.
+///
+/// Each source is followed by one or more handlers, which are called in order
+/// every time the source fires. The generated struct proves the bindings to
+/// the driver constructors.
+#[macro_export]
+macro_rules! bind_interrupts {
+    ($vis:vis struct $name:ident { $($irq:ident => $($handler:ty),*;)* }) => {
+        #[derive(Copy, Clone)]
+        $vis struct $name;
+
+        $(
+            #[allow(non_snake_case)]
+            #[no_mangle]
+            unsafe extern "C" fn $irq() {
+                $(
+                    <$handler as $crate::interrupt::typelevel::Handler<$crate::interrupt::typelevel::$irq>>::on_interrupt();
+                )*
+            }
+
+            $(
+                unsafe impl $crate::interrupt::typelevel::Binding<$crate::interrupt::typelevel::$irq, $handler> for $name {}
+            )*
+        )*
+    };
+}
@@ -6,11 +6,21 @@ use device::DeviceResources;
 // Import time driver
 mod time_driver;
 
+pub mod capture;
 pub mod clock;
 pub mod core;
+#[cfg(feature = "time")]
+pub mod counter;
+#[cfg(feature = "time")]
+pub mod debounce;
 pub mod device;
+mod fifo;
+pub mod flash;
 pub mod gpio;
+pub mod interrupt;
+pub mod mode;
 pub mod prelude;
+pub mod spi;
 pub mod time;
 pub mod uart;
 
@@ -0,0 +1,7 @@
+//! Driver modes (type state)
+
+/// Blocking driver mode
+pub struct Blocking;
+
+/// Interrupt driven driver mode
+pub struct Async;
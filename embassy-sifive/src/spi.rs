@@ -0,0 +1,736 @@
+//! Serial Peripheral Interface
+//!
+//! Master driver for the FE310 SPI blocks. Transfers are full duplex with
+//! 8-bit frames: every byte written to the TX FIFO clocks one byte into the
+//! RX FIFO, which is drained as the transfer goes.
+//!
+//! Async transfers refill the TX FIFO from the `txwm` interrupt and collect
+//! the last bytes from `rxwm`, so buffers of any length are streamed without
+//! blocking the executor.
+//!
+//! Devices on the bus are addressed through the hardware chip selects, see
+//! [`ExclusiveDevice`] and, for buses shared between drivers,
+//! [`SharedDevice`] and [`SharedAsyncDevice`].
+//!
+//! Only QSPI1 (the `SPI1` block of the datasheet) is supported. QSPI0 is
+//! wired to the boot flash, which the core executes from.
+
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::task::Poll;
+
+use e310x::{qspi0, Qspi1};
+use embassy_hal_internal::drop::OnDrop;
+use embassy_sync::waitqueue::AtomicWaker;
+use embedded_hal::spi::{Mode, Operation, Phase, Polarity, MODE_0};
+use riscv::register::mcycle;
+
+use crate::clock::Clocks;
+use crate::fifo::{self, listen_rx, listen_tx, DUMMY_BYTE, FIFO_DEPTH};
+use crate::gpio::{gpio0, IOF0};
+use crate::interrupt::{self, typelevel};
+use crate::time::Hertz;
+
+pub use crate::mode::{Async, Blocking};
+
+mod device;
+pub use device::{ExclusiveDevice, SharedAsyncDevice, SharedDevice};
+
+/// TX watermark of async transfers: `txwm` is pending while the TX FIFO holds
+/// fewer entries than this, leaving as many frame times to refill it
+const TX_REFILL_MARK: u8 = (FIFO_DEPTH / 2) as u8;
+
+/// Largest `sckdiv.div` value
+const MAX_DIVISOR: u32 = 0xfff;
+
+/// Bit order of a frame
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BitOrder {
+    /// Most significant bit first
+    MsbFirst,
+    /// Least significant bit first
+    LsbFirst,
+}
+
+/// SPI configuration
+#[derive(Clone, Copy)]
+pub struct Config {
+    /// Maximum SCK frequency, the closest lower frequency the divisor can
+    /// produce is used
+    pub frequency: Hertz,
+    /// Clock polarity and phase
+    pub mode: Mode,
+    /// Bit order of the frames
+    pub bit_order: BitOrder,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            frequency: Hertz(1_000_000),
+            mode: MODE_0,
+            bit_order: BitOrder::MsbFirst,
+        }
+    }
+}
+
+/// SPI configuration error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// The SCK frequency is below what the divisor can reach from the bus clock
+    FrequencyOutOfRange,
+}
+
+/// Returns the `sckdiv` value for the highest SCK frequency that does not
+/// exceed `frequency`, and that frequency.
+///
+/// SCK runs at `tlclk / (2 * (div + 1))`.
+pub(crate) fn sck_divisor(tlclk: Hertz, frequency: Hertz) -> Result<(u32, Hertz), ConfigError> {
+    let (tlclk, frequency) = (tlclk.0, frequency.0);
+    if frequency == 0 {
+        return Err(ConfigError::FrequencyOutOfRange);
+    }
+
+    let divisor = tlclk.div_ceil(2 * frequency).max(1);
+    if divisor - 1 > MAX_DIVISOR {
+        return Err(ConfigError::FrequencyOutOfRange);
+    }
+
+    Ok((divisor - 1, Hertz(tlclk / (2 * divisor))))
+}
+
+/// Validates `config` and applies it, leaving chip select under software
+/// control and the interrupts disabled.
+///
+/// Returns the achieved SCK frequency.
+fn configure<SPI: SpiX>(spi: &SPI, config: &Config, clocks: Clocks) -> Result<Hertz, ConfigError> {
+    let (div, frequency) = sck_divisor(clocks.tlclk(), config.frequency)?;
+
+    unsafe {
+        spi.ie().write(|w| w.txwm().bit(false).rxwm().bit(false));
+        spi.sckdiv().write(|w| w.div().bits(div as u16));
+    }
+    spi.sckmode().write(|w| {
+        w.pha()
+            .bit(config.mode.phase == Phase::CaptureOnSecondTransition)
+            .pol()
+            .bit(config.mode.polarity == Polarity::IdleHigh)
+    });
+    spi.csmode().write(|w| w.mode().off());
+    spi.fmt().write(|w| unsafe {
+        w.proto().single();
+        match config.bit_order {
+            BitOrder::MsbFirst => w.endian().big(),
+            BitOrder::LsbFirst => w.endian().little(),
+        };
+        w.dir().rx();
+        w.len().bits(8)
+    });
+    // txwm then means "TX FIFO empty", which blocking flushes rely on. Async
+    // transfers raise it to refill the FIFO before it runs dry.
+    spi.txmark().write(|w| unsafe { w.txmark().bits(1) });
+    spi.rxmark().write(|w| unsafe { w.rxmark().bits(0) });
+
+    Ok(frequency)
+}
+
+/// Pops a byte from the RX FIFO
+fn try_read(spi: &qspi0::RegisterBlock) -> Option<u8> {
+    let rxdata = spi.rxdata().read();
+    if rxdata.empty().bit_is_set() {
+        None
+    } else {
+        Some(rxdata.data().bits())
+    }
+}
+
+/// Pushes a byte into the TX FIFO, returns `false` if it is full
+fn try_write(spi: &qspi0::RegisterBlock, byte: u8) -> bool {
+    if spi.txdata().read().full().bit_is_set() {
+        false
+    } else {
+        spi.txdata().write(|w| unsafe { w.data().bits(byte) });
+        true
+    }
+}
+
+/// Asserts the hardware chip select `index` and keeps it asserted between frames
+fn select(spi: &qspi0::RegisterBlock, index: u32) {
+    spi.csid().write(|w| unsafe { w.bits(index) });
+    // All chip selects are active low
+    spi.csdef().reset();
+    spi.csmode().write(|w| w.mode().hold());
+}
+
+/// Releases the hardware chip select.
+///
+/// Must only be called once the last frame has been received. Frames still
+/// queued when a transaction is cancelled are shifted out with no chip
+/// select asserted.
+fn deselect(spi: &qspi0::RegisterBlock) {
+    spi.csmode().write(|w| w.mode().off());
+}
+
+/// Busy-waits for `ns` nanoseconds, counted in core clock cycles
+fn delay_ns(clocks: Clocks, ns: u32) {
+    let cycles = ns as u64 * clocks.coreclk().0 as u64 / 1_000_000_000;
+    let start = mcycle::read64();
+    while mcycle::read64().wrapping_sub(start) < cycles {}
+}
+
+/// Discards bytes left in the RX FIFO by an earlier transfer
+fn drain_rx(spi: &qspi0::RegisterBlock) {
+    while try_read(spi).is_some() {}
+}
+
+/// Source and destination of the bytes of one transfer
+trait Frame {
+    /// Number of bytes exchanged
+    fn len(&self) -> usize;
+    /// Byte to send at `index`
+    fn tx(&self, index: usize) -> u8;
+    /// Stores the byte received at `index`
+    fn rx(&mut self, index: usize, byte: u8);
+}
+
+struct ReadFrame<'a>(&'a mut [u8]);
+struct WriteFrame<'a>(&'a [u8]);
+struct TransferFrame<'a> {
+    read: &'a mut [u8],
+    write: &'a [u8],
+}
+struct InPlaceFrame<'a>(&'a mut [u8]);
+
+impl Frame for ReadFrame<'_> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+    fn tx(&self, _: usize) -> u8 {
+        DUMMY_BYTE
+    }
+    fn rx(&mut self, index: usize, byte: u8) {
+        self.0[index] = byte;
+    }
+}
+
+impl Frame for WriteFrame<'_> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+    fn tx(&self, index: usize) -> u8 {
+        self.0[index]
+    }
+    fn rx(&mut self, _: usize, _: u8) {}
+}
+
+impl Frame for TransferFrame<'_> {
+    fn len(&self) -> usize {
+        self.read.len().max(self.write.len())
+    }
+    fn tx(&self, index: usize) -> u8 {
+        self.write.get(index).copied().unwrap_or(DUMMY_BYTE)
+    }
+    fn rx(&mut self, index: usize, byte: u8) {
+        if let Some(slot) = self.read.get_mut(index) {
+            *slot = byte;
+        }
+    }
+}
+
+impl Frame for InPlaceFrame<'_> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+    fn tx(&self, index: usize) -> u8 {
+        self.0[index]
+    }
+    fn rx(&mut self, index: usize, byte: u8) {
+        self.0[index] = byte;
+    }
+}
+
+/// Progress of a transfer
+#[derive(Default)]
+struct Cursor {
+    sent: usize,
+    received: usize,
+}
+
+impl Cursor {
+    /// Moves bytes between `frame` and the FIFOs until neither side can make
+    /// progress. Returns `true` once every byte has been received.
+    ///
+    /// At most [`FIFO_DEPTH`] bytes are in flight, so the RX FIFO cannot overflow.
+    fn pump(&mut self, spi: &qspi0::RegisterBlock, frame: &mut impl Frame) -> bool {
+        let len = frame.len();
+        loop {
+            let mut progress = false;
+
+            if self.sent < len
+                && self.sent - self.received < FIFO_DEPTH
+                && try_write(spi, frame.tx(self.sent))
+            {
+                self.sent += 1;
+                progress = true;
+            }
+            if self.received < self.sent {
+                if let Some(byte) = try_read(spi) {
+                    frame.rx(self.received, byte);
+                    self.received += 1;
+                    progress = true;
+                }
+            }
+
+            if self.received == len {
+                return true;
+            }
+            if !progress {
+                return false;
+            }
+        }
+    }
+}
+
+/// SPI master
+pub struct Spi<SPI, PINS, MODE> {
+    spi: SPI,
+    pins: PINS,
+    frequency: Hertz,
+    clocks: Clocks,
+    /// Bytes still due on the RX side from a transfer that was cancelled
+    in_flight: usize,
+    _mode: PhantomData<MODE>,
+}
+
+impl<SPI: SpiX, PINS: Pins<SPI>> Spi<SPI, PINS, Blocking> {
+    /// Configures `spi` as a blocking bus master
+    pub fn new_blocking(
+        spi: SPI,
+        pins: PINS,
+        config: Config,
+        clocks: Clocks,
+    ) -> Result<Self, ConfigError> {
+        let frequency = configure(&spi, &config, clocks)?;
+
+        Ok(Spi {
+            spi,
+            pins,
+            frequency,
+            clocks,
+            in_flight: 0,
+            _mode: PhantomData,
+        })
+    }
+
+    fn blocking_transfer(&mut self, mut frame: impl Frame) {
+        drain_rx(&self.spi);
+        let mut cursor = Cursor::default();
+        while !cursor.pump(&self.spi, &mut frame) {}
+    }
+
+    /// Runs `operations` with the hardware chip select `cs` asserted
+    fn blocking_transaction(&mut self, cs: u32, operations: &mut [Operation<'_, u8>]) {
+        select(&self.spi, cs);
+        for operation in operations {
+            match operation {
+                Operation::Read(words) => self.blocking_transfer(ReadFrame(words)),
+                Operation::Write(words) => self.blocking_transfer(WriteFrame(words)),
+                Operation::Transfer(read, write) => {
+                    self.blocking_transfer(TransferFrame { read, write })
+                }
+                Operation::TransferInPlace(words) => self.blocking_transfer(InPlaceFrame(words)),
+                Operation::DelayNs(ns) => delay_ns(self.clocks, *ns),
+            }
+        }
+        deselect(&self.spi);
+    }
+}
+
+impl<SPI: SpiX, PINS: Pins<SPI>> Spi<SPI, PINS, Async> {
+    /// Configures `spi` as an interrupt driven bus master
+    pub fn new_async(
+        spi: SPI,
+        pins: PINS,
+        _irq: impl typelevel::Binding<SPI::Interrupt, InterruptHandler<SPI>>,
+        config: Config,
+        clocks: Clocks,
+    ) -> Result<Self, ConfigError> {
+        let frequency = configure(&spi, &config, clocks)?;
+        interrupt::enable::<SPI::Interrupt>();
+
+        Ok(Spi {
+            spi,
+            pins,
+            frequency,
+            clocks,
+            in_flight: 0,
+            _mode: PhantomData,
+        })
+    }
+
+    /// Streams `frame` through the FIFOs, waking up each time the TX FIFO
+    /// runs low and, once every byte has been sent, when the last one has
+    /// been received.
+    ///
+    /// If the future is dropped mid-transfer, the bytes still in flight are
+    /// left for [`Self::discard_in_flight`].
+    async fn async_transfer(&mut self, mut frame: impl Frame) {
+        drain_rx(&self.spi);
+        self.spi
+            .txmark()
+            .write(|w| unsafe { w.txmark().bits(TX_REFILL_MARK) });
+
+        let len = frame.len();
+        let mut cursor = Cursor::default();
+        poll_fn(|cx| {
+            SPI::state().waker.register(cx.waker());
+
+            if cursor.pump(&self.spi, &mut frame) {
+                self.in_flight = 0;
+                return Poll::Ready(());
+            }
+
+            self.in_flight = cursor.sent - cursor.received;
+            if cursor.sent < len {
+                // The bytes shifted out until the TX FIFO runs low wait in
+                // the RX FIFO, the next pump moves both sides
+                listen_tx(&self.spi);
+            } else {
+                self.listen_rx_all();
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Waits for the bytes of a cancelled transfer and discards them.
+    ///
+    /// Every byte sent is received once it has been shifted out, so the TX
+    /// FIFO and the shift register are empty as well when this returns.
+    /// Progress is kept in `in_flight`, so this can be cancelled as well.
+    async fn discard_in_flight(&mut self) {
+        poll_fn(|cx| {
+            SPI::state().waker.register(cx.waker());
+
+            while self.in_flight > 0 && try_read(&self.spi).is_some() {
+                self.in_flight -= 1;
+            }
+            if self.in_flight == 0 {
+                return Poll::Ready(());
+            }
+
+            self.listen_rx_all();
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Enables `rxwm` once all `in_flight` bytes have been received
+    fn listen_rx_all(&mut self) {
+        // rxwm is pending while the RX FIFO holds more than rxmark entries
+        let mark = self.in_flight.saturating_sub(1);
+        self.spi
+            .rxmark()
+            .write(|w| unsafe { w.rxmark().bits(mark as u8) });
+        listen_rx(&self.spi);
+    }
+
+    /// Runs `operations` with the hardware chip select `cs` asserted.
+    ///
+    /// If the future is dropped, the chip select is released right away and
+    /// the next transaction waits for the bytes left in flight before it
+    /// selects a device.
+    async fn async_transaction(&mut self, cs: u32, operations: &mut [Operation<'_, u8>]) {
+        self.discard_in_flight().await;
+        select(&self.spi, cs);
+        let _deselect = OnDrop::new(|| deselect(&unsafe { SPI::steal() }));
+        for operation in operations {
+            match operation {
+                Operation::Read(words) => self.async_transfer(ReadFrame(words)).await,
+                Operation::Write(words) => self.async_transfer(WriteFrame(words)).await,
+                Operation::Transfer(read, write) => {
+                    self.async_transfer(TransferFrame { read, write }).await
+                }
+                Operation::TransferInPlace(words) => self.async_transfer(InPlaceFrame(words)).await,
+                Operation::DelayNs(ns) => delay_ns(self.clocks, *ns),
+            }
+        }
+    }
+}
+
+impl<SPI: SpiX, PINS, MODE> Spi<SPI, PINS, MODE> {
+    /// Returns the SCK frequency actually achieved by the divisor
+    pub fn frequency(&self) -> Hertz {
+        self.frequency
+    }
+
+    /// Changes the SCK frequency, mode and bit order.
+    ///
+    /// Must not be called while a transfer is in progress.
+    pub fn set_config(&mut self, config: &Config) -> Result<(), ConfigError> {
+        self.frequency = configure(&self.spi, config, self.clocks)?;
+        Ok(())
+    }
+
+    /// Releases the SPI peripheral and its pins
+    pub fn free(self) -> (SPI, PINS) {
+        (self.spi, self.pins)
+    }
+}
+
+/// SPI error
+///
+/// The FE310 SPI has no error conditions, so no transfer can currently fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {}
+
+impl embedded_hal::spi::Error for Error {
+    fn kind(&self) -> embedded_hal::spi::ErrorKind {
+        match *self {}
+    }
+}
+
+impl<SPI, PINS, MODE> embedded_hal::spi::ErrorType for Spi<SPI, PINS, MODE> {
+    type Error = Error;
+}
+
+impl<SPI: SpiX, PINS: Pins<SPI>> embedded_hal::spi::SpiBus for Spi<SPI, PINS, Blocking> {
+    fn read(&mut self, words: &mut [u8]) -> Result<(), Error> {
+        self.blocking_transfer(ReadFrame(words));
+        Ok(())
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+        self.blocking_transfer(WriteFrame(words));
+        Ok(())
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Error> {
+        self.blocking_transfer(TransferFrame { read, write });
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Error> {
+        self.blocking_transfer(InPlaceFrame(words));
+        Ok(())
+    }
+
+    /// Waits until the TX FIFO is empty, although the last frame may still
+    /// be in the shift register when only [`FullDuplex`] writes were issued.
+    ///
+    /// [`FullDuplex`]: embedded_hal_nb::spi::FullDuplex
+    fn flush(&mut self) -> Result<(), Error> {
+        while self.spi.ip().read().txwm().bit_is_clear() {}
+        Ok(())
+    }
+}
+
+impl<SPI: SpiX, PINS: Pins<SPI>> embedded_hal_async::spi::SpiBus for Spi<SPI, PINS, Async> {
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Error> {
+        self.discard_in_flight().await;
+        self.async_transfer(ReadFrame(words)).await;
+        Ok(())
+    }
+
+    async fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+        self.discard_in_flight().await;
+        self.async_transfer(WriteFrame(words)).await;
+        Ok(())
+    }
+
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Error> {
+        self.discard_in_flight().await;
+        self.async_transfer(TransferFrame { read, write }).await;
+        Ok(())
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Error> {
+        self.discard_in_flight().await;
+        self.async_transfer(InPlaceFrame(words)).await;
+        Ok(())
+    }
+
+    /// Transfers only complete once every byte has been received, so there
+    /// is nothing left to wait for
+    async fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<SPI: SpiX, PINS: Pins<SPI>> embedded_hal_nb::spi::FullDuplex for Spi<SPI, PINS, Blocking> {
+    fn read(&mut self) -> nb::Result<u8, Error> {
+        try_read(&self.spi).ok_or(nb::Error::WouldBlock)
+    }
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Error> {
+        if try_write(&self.spi, word) {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+/// State shared between the driver of an SPI instance and its interrupt handler
+pub struct State {
+    waker: AtomicWaker,
+}
+
+impl State {
+    const fn new() -> Self {
+        State {
+            waker: AtomicWaker::new(),
+        }
+    }
+}
+
+static QSPI1_STATE: State = State::new();
+
+fn on_interrupt<SPI: SpiX>() {
+    let spi = unsafe { SPI::steal() };
+
+    let (tx, rx) = fifo::on_interrupt(&spi);
+    if tx || rx {
+        SPI::state().waker.wake();
+    }
+}
+
+/// SPI interrupt handler
+///
+/// Bind it to the source of the SPI instance with [`crate::bind_interrupts!`]
+/// to use the async driver.
+pub struct InterruptHandler<SPI> {
+    _spi: PhantomData<SPI>,
+}
+
+impl<SPI: SpiX> typelevel::Handler<SPI::Interrupt> for InterruptHandler<SPI> {
+    unsafe fn on_interrupt() {
+        on_interrupt::<SPI>();
+    }
+}
+
+/// SpiX trait extends the SPI peripheral
+pub trait SpiX: Deref<Target = qspi0::RegisterBlock> + private::Sealed {
+    /// Steals the SPI peripheral
+    ///
+    /// # Safety
+    ///
+    /// Using this function may break the guarantees of the singleton pattern.
+    unsafe fn steal() -> Self;
+
+    /// PLIC source of the SPI instance
+    type Interrupt: typelevel::Interrupt;
+
+    /// Returns the interrupt state of the SPI instance
+    fn state() -> &'static State;
+}
+
+impl SpiX for Qspi1 {
+    unsafe fn steal() -> Self {
+        Qspi1::steal()
+    }
+
+    type Interrupt = typelevel::QSPI1;
+
+    fn state() -> &'static State {
+        &QSPI1_STATE
+    }
+}
+
+/// SCK pin
+pub trait SckPin<SPI>: private::Sealed {}
+impl<T> SckPin<Qspi1> for gpio0::Pin5<IOF0<T>> {}
+/// MOSI pin, `()` for a receive-only bus
+pub trait MosiPin<SPI>: private::Sealed {}
+impl<T> MosiPin<Qspi1> for gpio0::Pin3<IOF0<T>> {}
+impl MosiPin<Qspi1> for () {}
+/// MISO pin, `()` for a transmit-only bus
+pub trait MisoPin<SPI>: private::Sealed {}
+impl<T> MisoPin<Qspi1> for gpio0::Pin4<IOF0<T>> {}
+impl MisoPin<Qspi1> for () {}
+
+/// Hardware chip select pin
+pub trait CsPin<SPI>: private::Sealed {
+    /// Chip select index written to `csid`
+    const INDEX: u32;
+}
+impl<T> CsPin<Qspi1> for gpio0::Pin2<IOF0<T>> {
+    const INDEX: u32 = 0;
+}
+impl<T> CsPin<Qspi1> for gpio0::Pin8<IOF0<T>> {
+    const INDEX: u32 = 1;
+}
+impl<T> CsPin<Qspi1> for gpio0::Pin9<IOF0<T>> {
+    const INDEX: u32 = 2;
+}
+impl<T> CsPin<Qspi1> for gpio0::Pin10<IOF0<T>> {
+    const INDEX: u32 = 3;
+}
+
+/// Bus pins as a `(MOSI, MISO, SCK)` tuple
+pub trait Pins<SPI>: private::Sealed {}
+impl<SPI, MOSI: MosiPin<SPI>, MISO: MisoPin<SPI>, SCK: SckPin<SPI>> Pins<SPI>
+    for (MOSI, MISO, SCK)
+{
+}
+
+// seal the "private" traits
+mod private {
+    use crate::gpio::{gpio0, IOF0};
+    use e310x::Qspi1;
+
+    pub trait Sealed {}
+
+    impl Sealed for Qspi1 {}
+    impl Sealed for () {}
+    impl<T> Sealed for gpio0::Pin2<IOF0<T>> {}
+    impl<T> Sealed for gpio0::Pin3<IOF0<T>> {}
+    impl<T> Sealed for gpio0::Pin4<IOF0<T>> {}
+    impl<T> Sealed for gpio0::Pin5<IOF0<T>> {}
+    impl<T> Sealed for gpio0::Pin8<IOF0<T>> {}
+    impl<T> Sealed for gpio0::Pin9<IOF0<T>> {}
+    impl<T> Sealed for gpio0::Pin10<IOF0<T>> {}
+    impl<MOSI, MISO, SCK> Sealed for (MOSI, MISO, SCK) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sck_divisor, ConfigError};
+    use crate::time::Hertz;
+
+    #[test]
+    fn divisor_never_exceeds_frequency() {
+        // 320 MHz / (2 * 160) = 1 MHz exactly
+        let (div, actual) = sck_divisor(Hertz(320_000_000), Hertz(1_000_000)).unwrap();
+        assert_eq!(div, 159);
+        assert_eq!(actual.0, 1_000_000);
+
+        // 16 MHz / (2 * 3 MHz) = 2.67, rounded up to 3
+        let (div, actual) = sck_divisor(Hertz(16_000_000), Hertz(3_000_000)).unwrap();
+        assert_eq!(div, 2);
+        assert_eq!(actual.0, 2_666_666);
+
+        // Faster than the bus allows, SCK runs at half the bus clock
+        let (div, actual) = sck_divisor(Hertz(16_000_000), Hertz(20_000_000)).unwrap();
+        assert_eq!(div, 0);
+        assert_eq!(actual.0, 8_000_000);
+    }
+
+    #[test]
+    fn divisor_out_of_range() {
+        // 320 MHz / (2 * 4096) = 39 kHz is the slowest SCK
+        assert!(sck_divisor(Hertz(320_000_000), Hertz(39_100)).is_ok());
+        assert_eq!(
+            sck_divisor(Hertz(320_000_000), Hertz(39_000)),
+            Err(ConfigError::FrequencyOutOfRange)
+        );
+        assert_eq!(
+            sck_divisor(Hertz(16_000_000), Hertz(0)),
+            Err(ConfigError::FrequencyOutOfRange)
+        );
+    }
+}
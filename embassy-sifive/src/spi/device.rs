@@ -0,0 +1,182 @@
+//! SPI devices with hardware chip select
+//!
+//! Every device is addressed through one of the `SS` pins of the SPI block.
+//! The chip select is held asserted (CSMODE HOLD) for the whole transaction
+//! and released afterwards, so frames of one transaction are never split.
+//!
+//! Shared devices lock the bus for the duration of a transaction and apply
+//! their own [`Config`] first, so devices with different clock settings can
+//! share one bus.
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::mutex::Mutex as AsyncMutex;
+use embedded_hal::spi::Operation;
+
+use super::{sck_divisor, Async, Blocking, Config, ConfigError, CsPin, Error, Pins, Spi, SpiX};
+use crate::clock::Clocks;
+
+/// Device that owns the whole bus
+pub struct ExclusiveDevice<SPI, PINS, CS, MODE> {
+    bus: Spi<SPI, PINS, MODE>,
+    cs: CS,
+}
+
+impl<SPI: SpiX, PINS: Pins<SPI>, CS: CsPin<SPI>, MODE> ExclusiveDevice<SPI, PINS, CS, MODE> {
+    /// Creates a device selected by `cs`
+    pub fn new(bus: Spi<SPI, PINS, MODE>, cs: CS) -> Self {
+        ExclusiveDevice { bus, cs }
+    }
+
+    /// Returns the underlying bus
+    pub fn bus_mut(&mut self) -> &mut Spi<SPI, PINS, MODE> {
+        &mut self.bus
+    }
+
+    /// Releases the bus and the chip select pin
+    pub fn free(self) -> (Spi<SPI, PINS, MODE>, CS) {
+        (self.bus, self.cs)
+    }
+}
+
+impl<SPI, PINS, CS, MODE> embedded_hal::spi::ErrorType for ExclusiveDevice<SPI, PINS, CS, MODE> {
+    type Error = Error;
+}
+
+impl<SPI: SpiX, PINS: Pins<SPI>, CS: CsPin<SPI>> embedded_hal::spi::SpiDevice
+    for ExclusiveDevice<SPI, PINS, CS, Blocking>
+{
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Error> {
+        self.bus.blocking_transaction(CS::INDEX, operations);
+        Ok(())
+    }
+}
+
+impl<SPI: SpiX, PINS: Pins<SPI>, CS: CsPin<SPI>> embedded_hal_async::spi::SpiDevice
+    for ExclusiveDevice<SPI, PINS, CS, Async>
+{
+    async fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Error> {
+        self.bus.async_transaction(CS::INDEX, operations).await;
+        Ok(())
+    }
+}
+
+/// Validates a device configuration against the bus clock
+fn check_config(config: Config, clocks: Clocks) -> Result<Config, ConfigError> {
+    sck_divisor(clocks.tlclk(), config.frequency)?;
+    Ok(config)
+}
+
+/// Blocking device on a bus shared through a blocking mutex.
+///
+/// The mutex is held for the whole transaction; with a critical section
+/// mutex, interrupts are disabled meanwhile.
+pub struct SharedDevice<'a, M: RawMutex, SPI, PINS, CS> {
+    bus: &'a Mutex<M, RefCell<Spi<SPI, PINS, Blocking>>>,
+    cs: CS,
+    config: Config,
+}
+
+impl<'a, M: RawMutex, SPI: SpiX, PINS: Pins<SPI>, CS: CsPin<SPI>>
+    SharedDevice<'a, M, SPI, PINS, CS>
+{
+    /// Creates a device selected by `cs` that applies `config` to the bus
+    /// before each transaction.
+    ///
+    /// The configuration is required because the bus keeps whatever the
+    /// previous device applied.
+    pub fn new(
+        bus: &'a Mutex<M, RefCell<Spi<SPI, PINS, Blocking>>>,
+        cs: CS,
+        config: Config,
+        clocks: Clocks,
+    ) -> Result<Self, ConfigError> {
+        Ok(SharedDevice {
+            bus,
+            cs,
+            config: check_config(config, clocks)?,
+        })
+    }
+
+    /// Releases the chip select pin
+    pub fn free(self) -> CS {
+        self.cs
+    }
+}
+
+impl<M: RawMutex, SPI, PINS, CS> embedded_hal::spi::ErrorType
+    for SharedDevice<'_, M, SPI, PINS, CS>
+{
+    type Error = Error;
+}
+
+impl<M: RawMutex, SPI: SpiX, PINS: Pins<SPI>, CS: CsPin<SPI>> embedded_hal::spi::SpiDevice
+    for SharedDevice<'_, M, SPI, PINS, CS>
+{
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Error> {
+        self.bus.lock(|bus| {
+            let mut bus = bus.borrow_mut();
+            let result = bus.set_config(&self.config);
+            debug_assert!(result.is_ok(), "validated when the device was created");
+            bus.blocking_transaction(CS::INDEX, operations);
+        });
+        Ok(())
+    }
+}
+
+/// Async device on a bus shared through an async mutex
+pub struct SharedAsyncDevice<'a, M: RawMutex, SPI, PINS, CS> {
+    bus: &'a AsyncMutex<M, Spi<SPI, PINS, Async>>,
+    cs: CS,
+    config: Config,
+}
+
+impl<'a, M: RawMutex, SPI: SpiX, PINS: Pins<SPI>, CS: CsPin<SPI>>
+    SharedAsyncDevice<'a, M, SPI, PINS, CS>
+{
+    /// Creates a device selected by `cs` that applies `config` to the bus
+    /// before each transaction.
+    ///
+    /// The configuration is required because the bus keeps whatever the
+    /// previous device applied.
+    pub fn new(
+        bus: &'a AsyncMutex<M, Spi<SPI, PINS, Async>>,
+        cs: CS,
+        config: Config,
+        clocks: Clocks,
+    ) -> Result<Self, ConfigError> {
+        Ok(SharedAsyncDevice {
+            bus,
+            cs,
+            config: check_config(config, clocks)?,
+        })
+    }
+
+    /// Releases the chip select pin
+    pub fn free(self) -> CS {
+        self.cs
+    }
+}
+
+impl<M: RawMutex, SPI, PINS, CS> embedded_hal::spi::ErrorType
+    for SharedAsyncDevice<'_, M, SPI, PINS, CS>
+{
+    type Error = Error;
+}
+
+impl<M: RawMutex, SPI: SpiX, PINS: Pins<SPI>, CS: CsPin<SPI>> embedded_hal_async::spi::SpiDevice
+    for SharedAsyncDevice<'_, M, SPI, PINS, CS>
+{
+    async fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Error> {
+        let mut bus = self.bus.lock().await;
+        // A transfer of another device that was cancelled may still be
+        // shifting out, which the new configuration must not affect
+        bus.discard_in_flight().await;
+        let result = bus.set_config(&self.config);
+        debug_assert!(result.is_ok(), "validated when the device was created");
+        bus.async_transaction(CS::INDEX, operations).await;
+        Ok(())
+    }
+}
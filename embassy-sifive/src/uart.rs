@@ -1,16 +1,35 @@
 use core::future::poll_fn;
 use core::task::Poll;
 use core::{marker::PhantomData, ops::Deref};
-use e310x::interrupt::{ExternalInterrupt, Priority};
-use e310x::{uart0, Uart0, PLIC};
+#[cfg(feature = "g002")]
+use e310x::Uart1;
+use e310x::{uart0, Uart0};
+use embassy_hal_internal::atomic_ring_buffer::RingBuffer;
+use embassy_hal_internal::drop::OnDrop;
 use embassy_sync::waitqueue::AtomicWaker;
-use embedded_hal_nb::serial::ErrorKind;
+#[cfg(feature = "time")]
+use embassy_time::{with_timeout, Duration, Instant};
 
-use crate::gpio::{gpio0, IOF0};
-use crate::{clock::Clocks, time::Bps};
+use crate::clock::Clocks;
+use crate::fifo::{self, listen_rx, listen_tx, FIFO_DEPTH};
+use crate::gpio::{gpio0, IntoUnknown, IOF0};
+use crate::interrupt::{self, typelevel};
+use crate::time::{Bps, Hertz};
+
+pub use crate::mode::{Async, Blocking};
+
+#[cfg(feature = "time")]
+mod auto_baud;
+#[cfg(feature = "time")]
+mod half_duplex;
+#[cfg(feature = "time")]
+pub use auto_baud::{AutoBaud, AutoBaudPin};
+#[cfg(feature = "time")]
+pub use half_duplex::{HalfDuplex, HalfDuplexConfig};
 
 pub struct Serial<UART, TX, RX, MODE> {
     uart: UART,
+    baud_rate: Bps,
     tx: Tx<UART, TX, MODE>,
     rx: Rx<UART, RX, MODE>,
 }
@@ -18,23 +37,186 @@ pub struct Serial<UART, TX, RX, MODE> {
 pub struct Tx<UART, TX, MODE> {
     uart: UART,
     pin: TX,
+    watermark: u8,
     _mode: PhantomData<MODE>,
 }
 
 pub struct Rx<UART, RX, MODE> {
     uart: UART,
     pin: RX,
+    watermark: u8,
+    /// Bytes a cancelled timed read already stored in the caller's buffer
+    #[cfg(feature = "time")]
+    pending: usize,
     _mode: PhantomData<MODE>,
 }
 
-static UART_WAKER: AtomicWaker = AtomicWaker::new();
+/// Number of stop bits
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StopBits {
+    /// One stop bit
+    One,
+    /// Two stop bits
+    Two,
+}
+
+/// UART configuration
+#[derive(Clone, Copy)]
+pub struct Config {
+    /// Baud rate
+    pub baud_rate: Bps,
+    /// Number of stop bits
+    pub stop_bits: StopBits,
+    /// `txwm` is pending while the TX FIFO holds fewer entries than this,
+    /// from 1 to 7. Higher values refill the FIFO before it runs dry.
+    pub tx_watermark: u8,
+    /// `rxwm` is pending while the RX FIFO holds more entries than this,
+    /// from 0 to 7. Bytes below the watermark stay in the FIFO until more
+    /// arrive; async reads adjust it per call.
+    pub rx_watermark: u8,
+    /// Maximum deviation of the achieved baud rate from `baud_rate`, in
+    /// hundredths of a percent
+    pub max_baud_error: u16,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            baud_rate: Bps(115_200),
+            stop_bits: StopBits::One,
+            tx_watermark: 1,
+            rx_watermark: 0,
+            max_baud_error: 200,
+        }
+    }
+}
+
+/// UART configuration error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// A watermark does not fit the FIFO depth, or the TX watermark is 0
+    InvalidWatermark,
+    /// No divisor reaches the baud rate within `max_baud_error` of the bus clock
+    BaudRateError,
+}
+
+impl Config {
+    fn validate(&self) -> Result<(), ConfigError> {
+        if !(1..FIFO_DEPTH as u8).contains(&self.tx_watermark)
+            || self.rx_watermark >= FIFO_DEPTH as u8
+        {
+            return Err(ConfigError::InvalidWatermark);
+        }
+        Ok(())
+    }
+}
+
+/// The receiver samples at 16x the baud rate, so `div + 1` must be at least 16
+const MIN_DIVISOR: u32 = 16;
+
+/// Returns the `div` value closest to `baud_rate` and the baud rate it achieves.
+///
+/// Fails if the divisor is out of range or the achieved rate deviates by more
+/// than `max_error` hundredths of a percent.
+fn baud_divisor(tlclk: Hertz, baud_rate: Bps, max_error: u16) -> Result<(u32, Bps), ConfigError> {
+    let (tlclk, baud) = (tlclk.0, baud_rate.0);
+    if baud == 0 {
+        return Err(ConfigError::BaudRateError);
+    }
+
+    let divisor = ((tlclk as u64 + baud as u64 / 2) / baud as u64) as u32;
+    if !(MIN_DIVISOR..=0x1_0000).contains(&divisor) {
+        return Err(ConfigError::BaudRateError);
+    }
+
+    let actual = (tlclk + divisor / 2) / divisor;
+    let error = (actual.abs_diff(baud) as u64 * 10_000) / baud as u64;
+    if error > max_error as u64 {
+        return Err(ConfigError::BaudRateError);
+    }
+
+    Ok((divisor - 1, Bps(actual)))
+}
+
+/// Validates `config` and applies it, leaving the UART interrupts disabled.
+///
+/// Returns the achieved baud rate.
+fn configure<UART: UartX>(
+    uart: &UART,
+    config: &Config,
+    clocks: Clocks,
+) -> Result<Bps, ConfigError> {
+    config.validate()?;
+
+    let (div, baud_rate) = baud_divisor(clocks.tlclk(), config.baud_rate, config.max_baud_error)?;
+    unsafe {
+        uart.ie().write(|w| w.txwm().bit(false).rxwm().bit(false));
+        uart.div().write(|w| w.bits(div));
+        uart.txctrl().write(|w| {
+            w.counter()
+                .bits(config.tx_watermark)
+                .nstop()
+                .bit(config.stop_bits == StopBits::Two)
+                .enable()
+                .bit(true)
+        });
+        uart.rxctrl()
+            .write(|w| w.counter().bits(config.rx_watermark).enable().bit(true));
+    }
+    Ok(baud_rate)
+}
+
+/// Sets the TX watermark.
+///
+/// `txwm` only means "TX FIFO empty" with a watermark of 1, which flushing relies on.
+fn set_tx_watermark(uart: &uart0::RegisterBlock, watermark: u8) {
+    uart.txctrl()
+        .modify(|_, w| unsafe { w.counter().bits(watermark) });
+}
+
+/// Sets the RX watermark, `rxwm` is pending while the FIFO holds more than
+/// `watermark` entries
+fn set_rx_watermark(uart: &uart0::RegisterBlock, watermark: u8) {
+    uart.rxctrl()
+        .modify(|_, w| unsafe { w.counter().bits(watermark) });
+}
+
+/// UART error
+///
+/// The FE310 UART does not detect parity, framing or overrun conditions, so
+/// no transfer can currently fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {}
+
+impl embedded_io::Error for Error {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match *self {}
+    }
+}
+
+impl embedded_hal_nb::serial::Error for Error {
+    fn kind(&self) -> embedded_hal_nb::serial::ErrorKind {
+        match *self {}
+    }
+}
+
+static UART0_STATE: State = State::new();
+#[cfg(feature = "g002")]
+static UART1_STATE: State = State::new();
 
 /// TX pin
-pub trait TxPin<UART>: private::Sealed {}
+pub trait TxPin<UART>: IntoUnknown + private::Sealed {}
 impl<T> TxPin<Uart0> for gpio0::Pin17<IOF0<T>> {}
+#[cfg(feature = "g002")]
+impl<T> TxPin<Uart1> for gpio0::Pin18<IOF0<T>> {}
 /// RX pin
-pub trait RxPin<UART>: private::Sealed {}
+pub trait RxPin<UART>: IntoUnknown + private::Sealed {}
 impl<T> RxPin<Uart0> for gpio0::Pin16<IOF0<T>> {}
+#[cfg(feature = "g002")]
+impl<T> RxPin<Uart1> for gpio0::Pin23<IOF0<T>> {}
 
 /// UartX trait extends the UART peripheral
 pub trait UartX: Deref<Target = uart0::RegisterBlock> + private::Sealed {
@@ -44,77 +226,116 @@ pub trait UartX: Deref<Target = uart0::RegisterBlock> + private::Sealed {
     ///
     /// Using this function may break the guarantees of the singleton pattern.
     unsafe fn steal() -> Self;
+
+    /// PLIC source of the UART
+    type Interrupt: typelevel::Interrupt;
+
+    /// Returns the interrupt state of the UART instance
+    fn state() -> &'static State;
 }
 
 impl UartX for Uart0 {
     unsafe fn steal() -> Self {
         Uart0::steal()
     }
+
+    type Interrupt = typelevel::UART0;
+
+    fn state() -> &'static State {
+        &UART0_STATE
+    }
 }
 
-pub struct Blocking;
-pub struct Async;
+/// On the HiFive1 Rev B, UART1 is wired to the ESP32 module
+#[cfg(feature = "g002")]
+impl UartX for Uart1 {
+    unsafe fn steal() -> Self {
+        Uart1::steal()
+    }
+
+    type Interrupt = typelevel::UART1;
+
+    fn state() -> &'static State {
+        &UART1_STATE
+    }
+}
 
 impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> Serial<UART, TX, RX, Blocking> {
-    pub fn new_blocking(uart: UART, pins: (TX, RX), baud_rate: Bps, clocks: Clocks) -> Self {
-        let div = clocks.tlclk().0 / baud_rate.0 - 1;
-        unsafe {
-            uart.ie().write(|w| w.txwm().bit(false).rxwm().bit(false));
-            uart.div().write(|w| w.bits(div));
-            uart.txctrl()
-                .write(|w| w.counter().bits(1).enable().bit(true));
-            uart.rxctrl().write(|w| w.enable().bit(true));
-        }
+    pub fn new_blocking(
+        uart: UART,
+        pins: (TX, RX),
+        config: Config,
+        clocks: Clocks,
+    ) -> Result<Self, ConfigError> {
+        let baud_rate = configure(&uart, &config, clocks)?;
 
         let tx = Tx {
             uart: unsafe { UART::steal() },
             pin: pins.0,
+            watermark: config.tx_watermark,
             _mode: PhantomData,
         };
         let rx = Rx {
             uart: unsafe { UART::steal() },
             pin: pins.1,
+            watermark: config.rx_watermark,
+            #[cfg(feature = "time")]
+            pending: 0,
             _mode: PhantomData,
         };
 
-        Serial { uart, tx, rx }
+        Ok(Serial {
+            uart,
+            baud_rate,
+            tx,
+            rx,
+        })
     }
 
-    pub fn read(&mut self) -> nb::Result<u8, ErrorKind> {
+    pub fn read(&mut self) -> nb::Result<u8, Error> {
         self.rx.blocking_read()
     }
 
-    pub fn write(&mut self, byte: u8) -> nb::Result<(), ErrorKind> {
+    pub fn write(&mut self, byte: u8) -> nb::Result<(), Error> {
         self.tx.blocking_write(byte)
     }
 
-    pub fn flush(&mut self) -> nb::Result<(), ErrorKind> {
+    pub fn flush(&mut self) -> nb::Result<(), Error> {
         self.tx.blocking_flush()
     }
 }
 
 impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> Serial<UART, TX, RX, Async> {
-    pub fn new_async(uart: UART, pins: (TX, RX), baud_rate: Bps, clocks: Clocks) -> Self {
-        let div = clocks.tlclk().0 / baud_rate.0 - 1;
-        unsafe {
-            uart.div().write(|w| w.bits(div));
-            uart.txctrl()
-                .write(|w| w.counter().bits(1).enable().bit(true));
-            uart.rxctrl().write(|w| w.enable().bit(true));
-        }
+    pub fn new_async(
+        uart: UART,
+        pins: (TX, RX),
+        _irq: impl typelevel::Binding<UART::Interrupt, InterruptHandler<UART>>,
+        config: Config,
+        clocks: Clocks,
+    ) -> Result<Self, ConfigError> {
+        let baud_rate = configure(&uart, &config, clocks)?;
 
         let tx = Tx {
             uart: unsafe { UART::steal() },
             pin: pins.0,
+            watermark: config.tx_watermark,
             _mode: PhantomData,
         };
         let rx = Rx {
             uart: unsafe { UART::steal() },
             pin: pins.1,
+            watermark: config.rx_watermark,
+            #[cfg(feature = "time")]
+            pending: 0,
             _mode: PhantomData,
         };
 
-        Serial { uart, tx, rx }
+        Ok(Serial {
+            uart,
+            baud_rate,
+            tx,
+            rx,
+        })
     }
 
     pub fn enable_interrupts(&self) {
@@ -126,46 +347,98 @@ impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> Serial<UART, TX, RX, Async>
             .ie()
             .write(|w| w.txwm().bit(false).rxwm().bit(true));
 
-        // Set UART interrupt priority
-        let priorities = PLIC::priorities();
-        priorities.reset::<ExternalInterrupt>();
-        unsafe { priorities.set_priority(ExternalInterrupt::UART0, Priority::P1) };
+        interrupt::enable::<UART::Interrupt>();
+    }
 
-        // Enable UART interrupt
-        let ctx = PLIC::ctx0();
-        unsafe {
-            ctx.threshold().set_threshold(Priority::P0);
-            ctx.enables().enable(ExternalInterrupt::UART0);
-            riscv::interrupt::enable();
-            PLIC::enable();
-        }
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.rx.read(buf).await
+    }
+
+    pub async fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        self.rx.read_exact(buf).await
     }
 
-    pub async fn read(&mut self, buf: &mut [u8]) -> Result<(), ErrorKind> {
-        self.rx.async_read(buf).await
+    #[cfg(feature = "time")]
+    pub async fn read_until_idle(
+        &mut self,
+        buf: &mut [u8],
+        idle: Duration,
+    ) -> Result<usize, Error> {
+        self.rx.read_until_idle(buf, idle).await
     }
 
-    pub async fn write(&mut self, byte: u8) -> nb::Result<(), ErrorKind> {
-        self.tx.async_write(byte).await
+    #[cfg(feature = "time")]
+    pub async fn read_with_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, Error> {
+        self.rx.read_with_timeout(buf, timeout).await
     }
 
-    pub async fn flush(&mut self) -> nb::Result<(), ErrorKind> {
-        self.tx.async_flush().await
+    pub async fn write(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.tx.write(buf).await
+    }
+
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        self.tx.flush().await
     }
 }
 
 impl<UART, TX, RX, MODE> Serial<UART, TX, RX, MODE> {
+    /// Returns the baud rate actually achieved by the divisor
+    pub fn baud_rate(&self) -> Bps {
+        self.baud_rate
+    }
+
     pub fn split(self) -> (Tx<UART, TX, MODE>, Rx<UART, RX, MODE>) {
         (self.tx, self.rx)
     }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>, MODE> Serial<UART, TX, RX, MODE> {
+    /// Disables the UART and releases it together with its pins.
+    ///
+    /// The pins are returned in their reset state (see [`IntoUnknown`]) so
+    /// they can be handed to another driver.
+    pub fn free(self) -> (UART, (TX::Unknown, RX::Unknown)) {
+        self.uart.ie().reset();
+        self.uart.txctrl().reset();
+        self.uart.rxctrl().reset();
+
+        (
+            self.uart,
+            (self.tx.pin.into_unknown(), self.rx.pin.into_unknown()),
+        )
+    }
+}
 
-    pub fn free(self) -> (UART, (TX, RX)) {
-        (self.uart, (self.tx.pin, self.rx.pin))
+impl<UART: UartX, PIN: TxPin<UART>, MODE> Tx<UART, PIN, MODE> {
+    /// Disables the transmitter and its interrupt and releases the pin in its
+    /// reset state.
+    ///
+    /// The UART itself is shared by both halves and is not returned.
+    pub fn free(self) -> PIN::Unknown {
+        critical_section::with(|_| self.uart.ie().modify(|_, w| w.txwm().clear_bit()));
+        self.uart.txctrl().reset();
+        self.pin.into_unknown()
+    }
+}
+
+impl<UART: UartX, PIN: RxPin<UART>, MODE> Rx<UART, PIN, MODE> {
+    /// Disables the receiver and its interrupt and releases the pin in its
+    /// reset state.
+    ///
+    /// The UART itself is shared by both halves and is not returned.
+    pub fn free(self) -> PIN::Unknown {
+        critical_section::with(|_| self.uart.ie().modify(|_, w| w.rxwm().clear_bit()));
+        self.uart.rxctrl().reset();
+        self.pin.into_unknown()
     }
 }
 
 impl<UART: UartX, PIN: RxPin<UART>> Rx<UART, PIN, Blocking> {
-    pub fn blocking_read(&mut self) -> nb::Result<u8, ErrorKind> {
+    pub fn blocking_read(&mut self) -> nb::Result<u8, Error> {
         let rxdata = self.uart.rxdata().read();
 
         if rxdata.empty().bit_is_set() {
@@ -177,7 +450,7 @@ impl<UART: UartX, PIN: RxPin<UART>> Rx<UART, PIN, Blocking> {
 }
 
 impl<UART: UartX, PIN: TxPin<UART>> Tx<UART, PIN, Blocking> {
-    pub fn blocking_write(&mut self, byte: u8) -> nb::Result<(), ErrorKind> {
+    pub fn blocking_write(&mut self, byte: u8) -> nb::Result<(), Error> {
         let txdata = self.uart.txdata().read();
 
         if txdata.full().bit_is_set() {
@@ -188,8 +461,12 @@ impl<UART: UartX, PIN: TxPin<UART>> Tx<UART, PIN, Blocking> {
         }
     }
 
-    pub fn blocking_flush(&mut self) -> nb::Result<(), ErrorKind> {
-        if self.uart.ip().read().txwm().bit_is_set() {
+    pub fn blocking_flush(&mut self) -> nb::Result<(), Error> {
+        set_tx_watermark(&self.uart, 1);
+        let empty = self.uart.ip().read().txwm().bit_is_set();
+        set_tx_watermark(&self.uart, self.watermark);
+
+        if empty {
             Ok(())
         } else {
             Err(nb::Error::WouldBlock)
@@ -198,68 +475,810 @@ impl<UART: UartX, PIN: TxPin<UART>> Tx<UART, PIN, Blocking> {
 }
 
 impl<UART: UartX, PIN: RxPin<UART>> Rx<UART, PIN, Async> {
-    pub async fn async_read(&mut self, buf: &mut [u8]) -> Result<(), ErrorKind> {
-        let result = poll_fn(|cx| {
-            UART_WAKER.register(cx.waker());
-
-            if self.uart.ip().read().rxwm().bit_is_set() {
-                let data = self.uart.rxdata().read().data().bits();
-                buf[0] = data;
-                // Enable receive interrupt
-                self.uart
-                    .ie()
-                    .write(|w| w.txwm().bit(false).rxwm().bit(true));
+    /// Reads the bytes currently available, waiting for at least one.
+    ///
+    /// Returns the number of bytes read, which is only 0 if `buf` is empty.
+    /// The RX watermark is lowered while waiting and restored to
+    /// [`Config::rx_watermark`] afterwards, also if the future is dropped.
+    ///
+    /// Forgets the bytes left over by a cancelled timed read.
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        #[cfg(feature = "time")]
+        {
+            self.pending = 0;
+        }
+        self.read_available(buf).await
+    }
+
+    async fn read_available(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let _restore = self.restore_watermark_on_drop();
+        self.set_watermark(1);
+        poll_fn(|cx| {
+            UART::state().rx_waker.register(cx.waker());
+
+            let n = self.drain(buf);
+            if n > 0 {
+                Poll::Ready(Ok(n))
+            } else {
+                listen_rx(&self.uart);
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Reads exactly `buf.len()` bytes.
+    ///
+    /// The RX watermark is raised to the number of missing bytes (up to the
+    /// FIFO depth), so the task is woken once per FIFO fill instead of once
+    /// per byte. It is restored to [`Config::rx_watermark`] afterwards.
+    pub async fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        let _restore = self.restore_watermark_on_drop();
+        let mut filled = 0;
+
+        poll_fn(|cx| {
+            UART::state().rx_waker.register(cx.waker());
+
+            filled += self.drain(&mut buf[filled..]);
+            if filled == buf.len() {
                 Poll::Ready(Ok(()))
             } else {
+                self.set_watermark(buf.len() - filled);
+                listen_rx(&self.uart);
                 Poll::Pending
             }
         })
-        .await;
+        .await
+    }
+
+    /// Reads a frame that ends when the line stays quiet for `idle`.
+    ///
+    /// Waits for the first byte without a time limit, then returns once `buf`
+    /// is full or no byte arrives for `idle`. Returns the number of bytes read.
+    ///
+    /// Cancel safe when the same `buf` is passed to the next timed read: if
+    /// the future is dropped, the number of bytes already stored in `buf` is
+    /// kept, and the next call continues after them and includes them in its
+    /// result.
+    ///
+    /// ```ignore
+    /// let mut frame = [0; 64];
+    /// loop {
+    ///     match select(rx.read_until_idle(&mut frame, idle), button.wait_for_low()).await {
+    ///         Either::First(n) => handle_frame(&frame[..n?]),
+    ///         // The bytes received so far are returned by the next call
+    ///         Either::Second(()) => toggle_led(),
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "time")]
+    pub async fn read_until_idle(
+        &mut self,
+        buf: &mut [u8],
+        idle: Duration,
+    ) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut n = self.take_pending(buf);
+        if n == 0 {
+            n = self.read_available(buf).await?;
+        }
+        while n < buf.len() {
+            self.pending = n;
+            match with_timeout(idle, self.read_available(&mut buf[n..])).await {
+                Ok(read) => n += read?,
+                Err(_) => break,
+            }
+        }
+        self.pending = 0;
+        Ok(n)
+    }
+
+    /// Reads until `buf` is full or `timeout` has elapsed.
+    ///
+    /// Returns the number of bytes read, which may be 0. Cancel safe in the
+    /// same way as [`Rx::read_until_idle`]; the next call starts a new
+    /// `timeout`.
+    #[cfg(feature = "time")]
+    pub async fn read_with_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize, Error> {
+        let deadline = Instant::now() + timeout;
+
+        let mut n = self.take_pending(buf);
+        while n < buf.len() {
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                break;
+            };
+            self.pending = n;
+            match with_timeout(remaining, self.read_available(&mut buf[n..])).await {
+                Ok(read) => n += read?,
+                Err(_) => break,
+            }
+        }
+        self.pending = 0;
+        Ok(n)
+    }
+
+    /// Returns the bytes a cancelled timed read left in `buf`
+    #[cfg(feature = "time")]
+    fn take_pending(&mut self, buf: &[u8]) -> usize {
+        core::mem::take(&mut self.pending).min(buf.len())
+    }
 
-        result
+    /// Moves bytes from the RX FIFO into `buf` until either is exhausted
+    fn drain(&mut self, buf: &mut [u8]) -> usize {
+        let mut n = 0;
+        while n < buf.len() {
+            let rxdata = self.uart.rxdata().read();
+            if rxdata.empty().bit_is_set() {
+                break;
+            }
+            buf[n] = rxdata.data().bits();
+            n += 1;
+        }
+        n
+    }
+
+    /// Makes `rxwm` pend once `bytes` are buffered, capped to the FIFO depth
+    fn set_watermark(&mut self, bytes: usize) {
+        let counter = bytes.clamp(1, FIFO_DEPTH) - 1;
+        set_rx_watermark(&self.uart, counter as u8);
+    }
+
+    /// Returns a guard that puts back the configured watermark
+    fn restore_watermark_on_drop(&self) -> OnDrop<impl FnOnce()> {
+        let watermark = self.watermark;
+        OnDrop::new(move || set_rx_watermark(&unsafe { UART::steal() }, watermark))
     }
 }
 
 impl<UART: UartX, PIN: TxPin<UART>> Tx<UART, PIN, Async> {
-    pub async fn async_write(&mut self, byte: u8) -> nb::Result<(), ErrorKind> {
-        let txdata = self.uart.txdata().read();
+    /// Writes the whole buffer, refilling the TX FIFO each time the transmit
+    /// watermark interrupt fires.
+    pub async fn write(&mut self, buf: &[u8]) -> Result<(), Error> {
+        let mut sent = 0;
+        set_tx_watermark(&self.uart, self.watermark);
 
-        if txdata.full().bit_is_set() {
-            Err(::nb::Error::WouldBlock)
-        } else {
-            self.uart.txdata().write(|w| unsafe { w.data().bits(byte) });
-            Ok(())
+        poll_fn(|cx| {
+            UART::state().tx_waker.register(cx.waker());
+
+            while sent < buf.len() && self.uart.txdata().read().full().bit_is_clear() {
+                self.uart
+                    .txdata()
+                    .write(|w| unsafe { w.data().bits(buf[sent]) });
+                sent += 1;
+            }
+
+            if sent == buf.len() {
+                Poll::Ready(Ok(()))
+            } else {
+                // Wake up once the FIFO drains below the watermark
+                listen_tx(&self.uart);
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Waits until the TX FIFO is empty, although the last character may
+    /// still be in the shift register.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        set_tx_watermark(&self.uart, 1);
+        poll_fn(|cx| {
+            UART::state().tx_waker.register(cx.waker());
+
+            if self.uart.ip().read().txwm().bit_is_set() {
+                set_tx_watermark(&self.uart, self.watermark);
+                Poll::Ready(Ok(()))
+            } else {
+                listen_tx(&self.uart);
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+/// State shared between the drivers of a UART instance and its interrupt handler
+pub struct State {
+    tx_waker: AtomicWaker,
+    rx_waker: AtomicWaker,
+    /// Only initialized while a [`BufferedUart`] owns the instance
+    rx_buf: RingBuffer,
+    tx_buf: RingBuffer,
+}
+
+impl State {
+    const fn new() -> Self {
+        State {
+            rx_buf: RingBuffer::new(),
+            tx_buf: RingBuffer::new(),
+            rx_waker: AtomicWaker::new(),
+            tx_waker: AtomicWaker::new(),
         }
     }
 
-    pub async fn async_flush(&mut self) -> nb::Result<(), ErrorKind> {
-        if self.uart.ip().read().txwm().bit_is_set() {
-            Ok(())
-        } else {
-            Err(nb::Error::WouldBlock)
+    /// Moves data between the FIFOs and the ring buffers
+    fn on_buffered_interrupt(&self, uart: &uart0::RegisterBlock) {
+        let ip = uart.ip().read();
+
+        if ip.rxwm().bit_is_set() {
+            let mut writer = unsafe { self.rx_buf.writer() };
+            let mut received = false;
+            // Leave bytes in the FIFO rather than dropping them when the ring is full
+            while !self.rx_buf.is_full() {
+                let rxdata = uart.rxdata().read();
+                if rxdata.empty().bit_is_set() {
+                    break;
+                }
+                writer.push_one(rxdata.data().bits());
+                received = true;
+            }
+            if self.rx_buf.is_full() {
+                // Re-enabled by the reader once it makes room
+                uart.ie().modify(|_, w| w.rxwm().clear_bit());
+            }
+            if received {
+                self.rx_waker.wake();
+            }
+        }
+
+        if ip.txwm().bit_is_set() {
+            let mut reader = unsafe { self.tx_buf.reader() };
+            while uart.txdata().read().full().bit_is_clear() {
+                match reader.pop_one() {
+                    Some(byte) => uart.txdata().write(|w| unsafe { w.data().bits(byte) }),
+                    None => break,
+                }
+            }
+            if self.tx_buf.is_empty() {
+                // Re-enabled by the writer once it queues more data
+                uart.ie().modify(|_, w| w.txwm().clear_bit());
+            }
+            self.tx_waker.wake();
+        }
+    }
+}
+
+/// Interrupt driven UART with user-provided RX and TX ring buffers.
+///
+/// The interrupt handler moves data between the hardware FIFOs and the ring
+/// buffers, so bytes keep being received while the reading task is busy.
+pub struct BufferedUart<UART, TX, RX> {
+    uart: UART,
+    baud_rate: Bps,
+    tx: BufferedTx<UART, TX>,
+    rx: BufferedRx<UART, RX>,
+}
+
+/// Transmit half of a [`BufferedUart`]
+pub struct BufferedTx<UART, TX> {
+    uart: UART,
+    pin: TX,
+    watermark: u8,
+}
+
+/// Receive half of a [`BufferedUart`]
+pub struct BufferedRx<UART, RX> {
+    uart: UART,
+    pin: RX,
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> BufferedUart<UART, TX, RX> {
+    /// Configures the UART and starts receiving into `rx_buffer`.
+    ///
+    /// `config.rx_watermark` is ignored: the interrupt fires for every
+    /// received byte, so none is left behind in the RX FIFO.
+    pub fn new(
+        uart: UART,
+        pins: (TX, RX),
+        _irq: impl typelevel::Binding<UART::Interrupt, InterruptHandler<UART>>,
+        config: Config,
+        clocks: Clocks,
+        tx_buffer: &'static mut [u8],
+        rx_buffer: &'static mut [u8],
+    ) -> Result<Self, ConfigError> {
+        let config = Config {
+            rx_watermark: 0,
+            ..config
+        };
+        let baud_rate = configure(&uart, &config, clocks)?;
+
+        let state = UART::state();
+        unsafe {
+            state.tx_buf.init(tx_buffer.as_mut_ptr(), tx_buffer.len());
+            state.rx_buf.init(rx_buffer.as_mut_ptr(), rx_buffer.len());
         }
+
+        // Receive continuously, transmit only while there is data queued
+        uart.ie().write(|w| w.txwm().bit(false).rxwm().bit(true));
+        interrupt::enable::<UART::Interrupt>();
+
+        let tx = BufferedTx {
+            uart: unsafe { UART::steal() },
+            pin: pins.0,
+            watermark: config.tx_watermark,
+        };
+        let rx = BufferedRx {
+            uart: unsafe { UART::steal() },
+            pin: pins.1,
+        };
+
+        Ok(BufferedUart {
+            uart,
+            baud_rate,
+            tx,
+            rx,
+        })
+    }
+
+    /// Returns the baud rate actually achieved by the divisor
+    pub fn baud_rate(&self) -> Bps {
+        self.baud_rate
+    }
+
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.rx.read(buf).await
+    }
+
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.tx.write(buf).await
+    }
+
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        self.tx.flush().await
+    }
+
+    pub fn split(self) -> (BufferedTx<UART, TX>, BufferedRx<UART, RX>) {
+        (self.tx, self.rx)
+    }
+
+    /// Disables the UART and its PLIC source, detaches the ring buffers and
+    /// releases the UART together with its pins in their reset state.
+    pub fn free(self) -> (UART, (TX::Unknown, RX::Unknown)) {
+        // The ISR must not touch the ring buffers once they are detached
+        <UART::Interrupt as typelevel::Interrupt>::disable();
+        self.uart.ie().reset();
+        self.uart.txctrl().reset();
+        self.uart.rxctrl().reset();
+
+        let state = UART::state();
+        unsafe {
+            state.tx_buf.deinit();
+            state.rx_buf.deinit();
+        }
+
+        (
+            self.uart,
+            (self.tx.pin.into_unknown(), self.rx.pin.into_unknown()),
+        )
+    }
+}
+
+impl<UART: UartX, RX: RxPin<UART>> BufferedRx<UART, RX> {
+    /// Reads the bytes currently buffered, waiting for at least one.
+    ///
+    /// Returns the number of bytes read, which is only 0 if `buf` is empty.
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let state = UART::state();
+        poll_fn(|cx| {
+            state.rx_waker.register(cx.waker());
+
+            let mut reader = unsafe { state.rx_buf.reader() };
+            let n = reader.pop(|data| {
+                let n = data.len().min(buf.len());
+                buf[..n].copy_from_slice(&data[..n]);
+                n
+            });
+
+            if n > 0 {
+                // There is room in the ring buffer again
+                listen_rx(&self.uart);
+                Poll::Ready(Ok(n))
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
     }
 }
 
-#[riscv_rt::external_interrupt(ExternalInterrupt::UART0)]
-fn interrupt_handler() {
-    let uart = unsafe { Uart0::steal() };
-    // Clear the interrupt
-    uart.ip().write(|w| w.rxwm().clear_bit());
-    // Disable receive interrupt
-    uart.ie().write(|w| w.rxwm().bit(false));
+impl<UART: UartX, TX: TxPin<UART>> BufferedTx<UART, TX> {
+    /// Queues as much of `buf` as fits into the ring buffer, waiting for room
+    /// for at least one byte.
+    ///
+    /// Returns the number of bytes queued, which is only 0 if `buf` is empty.
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let state = UART::state();
+        poll_fn(|cx| {
+            state.tx_waker.register(cx.waker());
+
+            let mut writer = unsafe { state.tx_buf.writer() };
+            let n = writer.push(|space| {
+                let n = space.len().min(buf.len());
+                space[..n].copy_from_slice(&buf[..n]);
+                n
+            });
+
+            if n > 0 {
+                // Let the interrupt handler move the data into the FIFO
+                set_tx_watermark(&self.uart, self.watermark);
+                listen_tx(&self.uart);
+                Poll::Ready(Ok(n))
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Waits until the ring buffer and the TX FIFO are empty
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        let state = UART::state();
+        set_tx_watermark(&self.uart, 1);
+        poll_fn(|cx| {
+            state.tx_waker.register(cx.waker());
+
+            if state.tx_buf.is_empty() && self.uart.ip().read().txwm().bit_is_set() {
+                set_tx_watermark(&self.uart, self.watermark);
+                Poll::Ready(Ok(()))
+            } else {
+                listen_tx(&self.uart);
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+impl<UART, TX, RX, MODE> embedded_hal_nb::serial::ErrorType for Serial<UART, TX, RX, MODE> {
+    type Error = Error;
+}
+
+impl<UART, PIN, MODE> embedded_hal_nb::serial::ErrorType for Tx<UART, PIN, MODE> {
+    type Error = Error;
+}
+
+impl<UART, PIN, MODE> embedded_hal_nb::serial::ErrorType for Rx<UART, PIN, MODE> {
+    type Error = Error;
+}
+
+impl<UART: UartX, PIN: RxPin<UART>> embedded_hal_nb::serial::Read<u8> for Rx<UART, PIN, Blocking> {
+    fn read(&mut self) -> nb::Result<u8, Error> {
+        self.blocking_read()
+    }
+}
+
+impl<UART: UartX, PIN: TxPin<UART>> embedded_hal_nb::serial::Write<u8> for Tx<UART, PIN, Blocking> {
+    fn write(&mut self, byte: u8) -> nb::Result<(), Error> {
+        self.blocking_write(byte)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Error> {
+        self.blocking_flush()
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> embedded_hal_nb::serial::Read<u8>
+    for Serial<UART, TX, RX, Blocking>
+{
+    fn read(&mut self) -> nb::Result<u8, Error> {
+        self.rx.blocking_read()
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> embedded_hal_nb::serial::Write<u8>
+    for Serial<UART, TX, RX, Blocking>
+{
+    fn write(&mut self, byte: u8) -> nb::Result<(), Error> {
+        self.tx.blocking_write(byte)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Error> {
+        self.tx.blocking_flush()
+    }
+}
+
+impl<UART, TX, RX, MODE> embedded_io::ErrorType for Serial<UART, TX, RX, MODE> {
+    type Error = Error;
+}
+
+impl<UART, PIN, MODE> embedded_io::ErrorType for Tx<UART, PIN, MODE> {
+    type Error = Error;
+}
 
-    UART_WAKER.wake();
+impl<UART, PIN, MODE> embedded_io::ErrorType for Rx<UART, PIN, MODE> {
+    type Error = Error;
+}
+
+impl<UART: UartX, PIN: RxPin<UART>> embedded_io::Read for Rx<UART, PIN, Blocking> {
+    /// Blocks until at least one byte is received, then returns every byte
+    /// already in the RX FIFO that fits into `buf`
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        buf[0] = nb::block!(self.blocking_read())?;
+        let mut n = 1;
+        while n < buf.len() {
+            match self.blocking_read() {
+                Ok(byte) => buf[n] = byte,
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(e)) => return Err(e),
+            }
+            n += 1;
+        }
+        Ok(n)
+    }
+}
+
+impl<UART: UartX, PIN: TxPin<UART>> embedded_io::Write for Tx<UART, PIN, Blocking> {
+    /// Blocks until at least one byte is queued, then queues as many bytes
+    /// as fit into the TX FIFO
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        nb::block!(self.blocking_write(buf[0]))?;
+        let mut n = 1;
+        while n < buf.len() {
+            match self.blocking_write(buf[n]) {
+                Ok(()) => n += 1,
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(e)) => return Err(e),
+            }
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        nb::block!(self.blocking_flush())
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> embedded_io::Read
+    for Serial<UART, TX, RX, Blocking>
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        embedded_io::Read::read(&mut self.rx, buf)
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> embedded_io::Write
+    for Serial<UART, TX, RX, Blocking>
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        embedded_io::Write::write(&mut self.tx, buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        embedded_io::Write::flush(&mut self.tx)
+    }
+}
+
+impl<UART: UartX, PIN: RxPin<UART>> embedded_io_async::Read for Rx<UART, PIN, Async> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        Rx::read(self, buf).await
+    }
+
+    async fn read_exact(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Result<(), embedded_io::ReadExactError<Error>> {
+        Ok(Rx::read_exact(self, buf).await?)
+    }
+}
+
+impl<UART: UartX, PIN: TxPin<UART>> embedded_io_async::Write for Tx<UART, PIN, Async> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        Tx::write(self, buf).await?;
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        Tx::flush(self).await
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> embedded_io_async::Read
+    for Serial<UART, TX, RX, Async>
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.rx.read(buf).await
+    }
+
+    async fn read_exact(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Result<(), embedded_io::ReadExactError<Error>> {
+        Ok(self.rx.read_exact(buf).await?)
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> embedded_io_async::Write
+    for Serial<UART, TX, RX, Async>
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.tx.write(buf).await?;
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        self.tx.flush().await
+    }
+}
+
+impl<UART, TX, RX> embedded_io::ErrorType for BufferedUart<UART, TX, RX> {
+    type Error = Error;
+}
+
+impl<UART, PIN> embedded_io::ErrorType for BufferedTx<UART, PIN> {
+    type Error = Error;
+}
+
+impl<UART, PIN> embedded_io::ErrorType for BufferedRx<UART, PIN> {
+    type Error = Error;
+}
+
+impl<UART: UartX, PIN: RxPin<UART>> embedded_io_async::Read for BufferedRx<UART, PIN> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        BufferedRx::read(self, buf).await
+    }
+}
+
+impl<UART: UartX, PIN: TxPin<UART>> embedded_io_async::Write for BufferedTx<UART, PIN> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        BufferedTx::write(self, buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        BufferedTx::flush(self).await
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> embedded_io_async::Read
+    for BufferedUart<UART, TX, RX>
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.rx.read(buf).await
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> embedded_io_async::Write
+    for BufferedUart<UART, TX, RX>
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.tx.write(buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        self.tx.flush().await
+    }
+}
+
+fn on_interrupt<UART: UartX>() {
+    let uart = unsafe { UART::steal() };
+    let state = UART::state();
+
+    if state.rx_buf.is_available() {
+        state.on_buffered_interrupt(&uart);
+        return;
+    }
+
+    let (tx, rx) = fifo::on_interrupt(&uart);
+    if tx {
+        state.tx_waker.wake();
+    }
+    if rx {
+        state.rx_waker.wake();
+    }
+}
+
+/// UART interrupt handler
+///
+/// Bind it to the source of the UART with [`crate::bind_interrupts!`] to use
+/// the async and buffered drivers.
+pub struct InterruptHandler<UART> {
+    _uart: PhantomData<UART>,
+}
+
+impl<UART: UartX> typelevel::Handler<UART::Interrupt> for InterruptHandler<UART> {
+    unsafe fn on_interrupt() {
+        on_interrupt::<UART>();
+    }
 }
 
 // seal the "private" traits
 mod private {
-    use crate::gpio::{gpio0, IOF0};
+    use crate::gpio::{gpio0, Input, IOF0};
     use e310x::Uart0;
+    #[cfg(feature = "g002")]
+    use e310x::Uart1;
 
     pub trait Sealed {}
 
     impl Sealed for Uart0 {}
     impl<T> Sealed for gpio0::Pin17<IOF0<T>> {}
     impl<T> Sealed for gpio0::Pin16<IOF0<T>> {}
+    impl<T> Sealed for gpio0::Pin16<Input<T>> {}
+
+    #[cfg(feature = "g002")]
+    impl Sealed for Uart1 {}
+    #[cfg(feature = "g002")]
+    impl<T> Sealed for gpio0::Pin18<IOF0<T>> {}
+    #[cfg(feature = "g002")]
+    impl<T> Sealed for gpio0::Pin23<IOF0<T>> {}
+    #[cfg(feature = "g002")]
+    impl<T> Sealed for gpio0::Pin23<Input<T>> {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{baud_divisor, ConfigError};
+    use crate::time::{Bps, Hertz};
+
+    #[test]
+    fn divisor_rounds_to_nearest() {
+        // 320 MHz / 115200 = 2777.8
+        let (div, actual) = baud_divisor(Hertz(320_000_000), Bps(115_200), 200).unwrap();
+        assert_eq!(div, 2777);
+        assert_eq!(actual.0, 115_191);
+
+        // 16 MHz / 115200 = 138.9, truncating used to give div 137 and a 0.6% error
+        let (div, actual) = baud_divisor(Hertz(16_000_000), Bps(115_200), 200).unwrap();
+        assert_eq!(div, 138);
+        assert_eq!(actual.0, 115_108);
+    }
+
+    #[test]
+    fn divisor_error_past_tolerance() {
+        // 13.8 MHz / 460800 = 29.95 -> 30, 0.17% error
+        assert!(baud_divisor(Hertz(13_800_000), Bps(460_800), 200).is_ok());
+        assert_eq!(
+            baud_divisor(Hertz(13_800_000), Bps(460_800), 10),
+            Err(ConfigError::BaudRateError)
+        );
+    }
+
+    #[test]
+    fn divisor_out_of_range() {
+        // Below the 16x oversampling limit
+        assert_eq!(
+            baud_divisor(Hertz(13_800_000), Bps(1_000_000), 10_000),
+            Err(ConfigError::BaudRateError)
+        );
+        // Baud rate above the bus clock
+        assert_eq!(
+            baud_divisor(Hertz(1_000_000), Bps(2_000_000), 10_000),
+            Err(ConfigError::BaudRateError)
+        );
+        // Divisor does not fit the 16-bit register
+        assert_eq!(
+            baud_divisor(Hertz(320_000_000), Bps(300), 10_000),
+            Err(ConfigError::BaudRateError)
+        );
+        assert_eq!(
+            baud_divisor(Hertz(16_000_000), Bps(0), 10_000),
+            Err(ConfigError::BaudRateError)
+        );
+    }
 }
@@ -0,0 +1,254 @@
+//! Baud rate detection
+//!
+//! While detecting, the RX pin is a GPIO input whose edges are timestamped
+//! against `mcycle` by [`InputCapture`]. The falling edge of the first start
+//! bit opens a character frame. The shortest interval between two edges of
+//! that frame is taken as the bit time, so the sender should transmit a
+//! character with at least one isolated bit, such as `'\r'` or `'U'`.
+//!
+//! Once a rate is known the pin is switched back to IOF0 and the UART is
+//! configured at that rate through the usual [`Serial`] constructors.
+
+use embassy_time::{Duration, Timer};
+
+use super::{Async, Blocking, Config, ConfigError, InterruptHandler, RxPin, Serial, TxPin, UartX};
+use crate::capture::{CaptureBuffer, InputCapture, Span, Timebase};
+use crate::clock::Clocks;
+use crate::gpio::{self, gpio0, Edge, Input, InterruptPin, NoInvert, IOF0};
+use crate::interrupt::typelevel::Binding;
+use crate::time::Bps;
+use e310x::Uart0;
+#[cfg(feature = "g002")]
+use e310x::Uart1;
+
+/// Bits in a frame before the stop bit: start bit and 8 data bits
+const FRAME_BITS: u64 = 9;
+
+/// Baud rates the measurement is snapped to
+const STANDARD_BAUD_RATES: [u32; 12] = [
+    1_200, 2_400, 4_800, 9_600, 19_200, 38_400, 57_600, 115_200, 230_400, 460_800, 921_600,
+    1_000_000,
+];
+
+/// Maximum deviation from a standard rate that is still snapped to it, in
+/// hundredths of a percent
+const SNAP_TOLERANCE: u64 = 500;
+
+/// RX pins in GPIO input mode, which can be handed back to the UART
+pub trait AutoBaudPin<UART>: InterruptPin + super::private::Sealed {
+    /// The pin in IOF0 mode
+    type Rx: RxPin<UART>;
+
+    /// Switches the pin back to the UART
+    fn into_rx(self) -> Self::Rx;
+}
+
+impl<MODE> AutoBaudPin<Uart0> for gpio0::Pin16<Input<MODE>> {
+    type Rx = gpio0::Pin16<IOF0<NoInvert>>;
+
+    fn into_rx(self) -> Self::Rx {
+        self.into_iof0()
+    }
+}
+
+#[cfg(feature = "g002")]
+impl<MODE> AutoBaudPin<Uart1> for gpio0::Pin23<Input<MODE>> {
+    type Rx = gpio0::Pin23<IOF0<NoInvert>>;
+
+    fn into_rx(self) -> Self::Rx {
+        self.into_iof0()
+    }
+}
+
+/// Returns the baud rate matching a bit time of `bit_ticks` cycles of a
+/// `tick_hz` counter, snapped to a standard rate when one is close enough
+fn estimate_baud_rate(bit_ticks: u64, tick_hz: u32) -> Bps {
+    let tick_hz = tick_hz as u64;
+    let measured = (tick_hz + bit_ticks / 2) / bit_ticks.max(1);
+
+    let nearest = STANDARD_BAUD_RATES
+        .iter()
+        .map(|&rate| rate as u64)
+        .min_by_key(|rate| rate.abs_diff(measured))
+        .unwrap();
+    if nearest.abs_diff(measured) * 10_000 <= nearest * SNAP_TOLERANCE {
+        Bps(nearest as u32)
+    } else {
+        Bps(measured as u32)
+    }
+}
+
+/// Shortest interval between consecutive edges that come at most `window`
+/// ticks after the edge at `start`
+fn shortest_interval(start: u64, window: u64, edges: impl Iterator<Item = u64>) -> u64 {
+    let mut previous = start;
+    let mut shortest = u64::MAX;
+
+    for ticks in edges {
+        if ticks.wrapping_sub(start) > window {
+            break;
+        }
+        shortest = shortest.min(ticks.wrapping_sub(previous));
+        previous = ticks;
+    }
+
+    shortest
+}
+
+/// UART whose RX pin is temporarily used to measure the baud rate
+pub struct AutoBaud<UART, TX, PIN: InterruptPin> {
+    uart: UART,
+    tx: TX,
+    capture: InputCapture<PIN>,
+    clocks: Clocks,
+    /// Rate returned by the last [`AutoBaud::measure`]
+    baud_rate: Option<Bps>,
+}
+
+impl<UART: UartX, TX: TxPin<UART>, PIN: AutoBaudPin<UART>> AutoBaud<UART, TX, PIN> {
+    /// Starts timestamping the edges of the RX pin into `buffer`.
+    ///
+    /// The pin should idle high, either driven by the sender or pulled up.
+    pub fn new(
+        uart: UART,
+        pins: (TX, PIN),
+        irq: impl Binding<PIN::Interrupt, gpio::InterruptHandler>,
+        buffer: &'static CaptureBuffer,
+        clocks: Clocks,
+    ) -> Self {
+        let capture = InputCapture::new(pins.1, irq, Edge::Both, Timebase::Mcycle, buffer, clocks);
+
+        AutoBaud {
+            uart,
+            tx: pins.0,
+            capture,
+            clocks,
+            baud_rate: None,
+        }
+    }
+
+    /// Waits for the next character and returns the baud rate it was sent at.
+    ///
+    /// The future can be dropped, e.g. by a timeout, and polled again later.
+    pub async fn measure(&mut self) -> Bps {
+        loop {
+            self.capture.clear();
+
+            // The start bit begins with a falling edge
+            let start = self.capture.next_edge().await;
+            if start.edge != Edge::Falling {
+                continue;
+            }
+            let first = self.capture.next_edge().await;
+            if first.edge != Edge::Rising {
+                continue;
+            }
+
+            // The first run holds at least one bit, so the data bits have
+            // been shifted in after this long
+            let first_run = first.ticks.wrapping_sub(start.ticks);
+            let frame = Span {
+                ticks: first_run * FRAME_BITS,
+                tick_hz: self.capture.tick_hz(),
+            };
+            Timer::after(Duration::from_micros(frame.as_micros())).await;
+
+            if self.capture.overrun() {
+                continue;
+            }
+            let edges = core::iter::from_fn(|| self.capture.try_next())
+                .take_while(|entry| entry.edge != Edge::Both)
+                .map(|entry| entry.ticks);
+            let bit_ticks =
+                shortest_interval(first.ticks, frame.ticks - first_run, edges).min(first_run);
+
+            let baud_rate = estimate_baud_rate(bit_ticks, self.capture.tick_hz());
+            self.baud_rate = Some(baud_rate);
+            return baud_rate;
+        }
+    }
+
+    /// Hands the RX pin back to the UART and configures a blocking [`Serial`].
+    ///
+    /// `config.baud_rate` is replaced by the last measured rate, if any.
+    pub fn into_blocking(
+        self,
+        config: Config,
+    ) -> Result<Serial<UART, TX, PIN::Rx, Blocking>, ConfigError> {
+        let config = with_measured_rate(config, self.baud_rate);
+        let (uart, pins, clocks) = self.release();
+        Serial::new_blocking(uart, pins, config, clocks)
+    }
+
+    /// Hands the RX pin back to the UART and configures an async [`Serial`].
+    ///
+    /// `config.baud_rate` is replaced by the last measured rate, if any.
+    pub fn into_async(
+        self,
+        irq: impl Binding<UART::Interrupt, InterruptHandler<UART>>,
+        config: Config,
+    ) -> Result<Serial<UART, TX, PIN::Rx, Async>, ConfigError> {
+        let config = with_measured_rate(config, self.baud_rate);
+        let (uart, pins, clocks) = self.release();
+        Serial::new_async(uart, pins, irq, config, clocks)
+    }
+
+    fn release(self) -> (UART, (TX, PIN::Rx), Clocks) {
+        let rx = self.capture.release().into_rx();
+        (self.uart, (self.tx, rx), self.clocks)
+    }
+}
+
+/// Returns `config` running at `measured`, if a rate was detected
+fn with_measured_rate(config: Config, measured: Option<Bps>) -> Config {
+    Config {
+        baud_rate: measured.unwrap_or(config.baud_rate),
+        ..config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{estimate_baud_rate, shortest_interval, with_measured_rate};
+    use crate::time::{Bps, Hertz};
+    use crate::uart::{baud_divisor, Config};
+
+    #[test]
+    fn snaps_to_standard_rates() {
+        // 320 MHz core clock, 115200 baud is 2777.8 cycles per bit
+        assert_eq!(estimate_baud_rate(2_790, 320_000_000).0, 115_200);
+        assert_eq!(estimate_baud_rate(33_333, 320_000_000).0, 9_600);
+        // 250 kbaud is 8% away from the nearest standard rate
+        assert_eq!(estimate_baud_rate(1_280, 320_000_000).0, 250_000);
+    }
+
+    #[test]
+    fn shortest_interval_within_frame() {
+        // '\r' at 100 ticks per bit: start, 1, 0, 1, 1, 0, 0, 0, 0, stop.
+        // The start bit ends at 100, edges follow at 200, 300, 500 and 900.
+        let edges = [200, 300, 500, 900];
+        assert_eq!(shortest_interval(100, 800, edges.iter().copied()), 100);
+
+        // An edge of the next character is ignored
+        let edges = [300, 400, 1_500];
+        assert_eq!(shortest_interval(100, 800, edges.iter().copied()), 100);
+    }
+
+    #[test]
+    fn measured_rate_reaches_divisor() {
+        let config = Config {
+            baud_rate: Bps(9_600),
+            ..Config::default()
+        };
+        let measured = estimate_baud_rate(2_790, 320_000_000);
+
+        let config = with_measured_rate(config, Some(measured));
+        let (div, _) =
+            baud_divisor(Hertz(320_000_000), config.baud_rate, config.max_baud_error).unwrap();
+        assert_eq!(div, 2777);
+
+        // Without a measurement the configured rate is kept
+        let config = with_measured_rate(Config::default(), None);
+        assert_eq!(config.baud_rate.0, 115_200);
+    }
+}
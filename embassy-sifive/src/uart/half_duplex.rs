@@ -0,0 +1,154 @@
+//! RS-485 half-duplex mode
+//!
+//! RS-485 transceivers only drive the bus while their DE (driver enable)
+//! input is asserted. [`HalfDuplex`] owns the GPIO wired to DE (and usually
+//! to the inverted RE as well): it raises DE before the first byte of a frame
+//! and drops it once the last stop bit has left the shift register.
+//!
+//! The end of transmission is detected in two steps. `ip.txwm` with a TX
+//! watermark of 1 signals that the FIFO has drained to 0 entries, and the
+//! byte that was moved into the shift register at that point takes one more
+//! character time to go out.
+
+use core::convert::Infallible;
+
+use embassy_hal_internal::drop::OnDrop;
+use embassy_time::{block_for, Duration, Timer};
+use embedded_hal::digital::{OutputPin, PinState};
+
+use super::{Async, Blocking, Error, RxPin, Serial, TxPin, UartX};
+
+/// Half-duplex configuration
+#[derive(Clone, Copy, Default)]
+pub struct HalfDuplexConfig {
+    /// Extra time DE stays asserted after the last stop bit, before the bus
+    /// is handed back to the other nodes
+    pub turnaround: Duration,
+}
+
+/// Half-duplex UART driving the DE pin of an RS-485 transceiver
+pub struct HalfDuplex<UART, TX, RX, DE, MODE> {
+    serial: Serial<UART, TX, RX, MODE>,
+    de: DE,
+    /// Time to shift out one character: start bit, 8 data bits and stop bits
+    char_time: Duration,
+    config: HalfDuplexConfig,
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>, DE, MODE> HalfDuplex<UART, TX, RX, DE, MODE>
+where
+    DE: OutputPin<Error = Infallible>,
+{
+    /// Wraps `serial`, releasing the bus until the first write
+    pub fn new(serial: Serial<UART, TX, RX, MODE>, de: DE, config: HalfDuplexConfig) -> Self {
+        let stop_bits = if serial.uart.txctrl().read().nstop().bit_is_set() {
+            2
+        } else {
+            1
+        };
+        let bits = 1 + 8 + stop_bits;
+        let baud = serial.baud_rate.0 as u64;
+        let char_time = Duration::from_micros((bits * 1_000_000).div_ceil(baud));
+
+        let mut half_duplex = HalfDuplex {
+            serial,
+            de,
+            char_time,
+            config,
+        };
+        half_duplex.drive(false);
+        half_duplex
+    }
+
+    /// Changes the turnaround delay
+    pub fn set_turnaround(&mut self, turnaround: Duration) {
+        self.config.turnaround = turnaround;
+    }
+
+    /// Releases the bus and returns the UART and the DE pin
+    pub fn free(mut self) -> (Serial<UART, TX, RX, MODE>, DE) {
+        self.drive(false);
+        (self.serial, self.de)
+    }
+
+    /// Time DE is held after the TX FIFO has drained
+    fn release_delay(&self) -> Duration {
+        self.char_time + self.config.turnaround
+    }
+
+    fn drive(&mut self, active: bool) {
+        set_de(&mut self.de, active);
+    }
+}
+
+fn set_de<DE: OutputPin<Error = Infallible>>(de: &mut DE, active: bool) {
+    match de.set_state(PinState::from(active)) {
+        Ok(()) => {}
+        Err(e) => match e {},
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>, DE> HalfDuplex<UART, TX, RX, DE, Blocking>
+where
+    DE: OutputPin<Error = Infallible>,
+{
+    /// Receives a byte, see [`Serial::read`]
+    pub fn read(&mut self) -> nb::Result<u8, Error> {
+        self.serial.read()
+    }
+
+    /// Transmits `buf` as one frame, asserting DE for its whole duration
+    pub fn write(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.drive(true);
+
+        for byte in buf {
+            nb::block!(self.serial.write(*byte))?;
+        }
+        nb::block!(self.serial.flush())?;
+
+        block_for(self.release_delay());
+        self.drive(false);
+        Ok(())
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>, DE> HalfDuplex<UART, TX, RX, DE, Async>
+where
+    DE: OutputPin<Error = Infallible>,
+{
+    /// Receives into `buf`, see [`Serial::read`]
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.serial.read(buf).await
+    }
+
+    /// Fills `buf` completely, see [`Serial::read_exact`]
+    pub async fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        self.serial.read_exact(buf).await
+    }
+
+    /// Receives until the line is idle, see [`Serial::read_until_idle`]
+    pub async fn read_until_idle(
+        &mut self,
+        buf: &mut [u8],
+        idle: Duration,
+    ) -> Result<usize, Error> {
+        self.serial.read_until_idle(buf, idle).await
+    }
+
+    /// Transmits `buf` as one frame, asserting DE for its whole duration.
+    ///
+    /// If the future is dropped before completion, DE is released right away
+    /// and the rest of the frame goes out without driving the bus.
+    pub async fn write(&mut self, buf: &[u8]) -> Result<(), Error> {
+        let release_delay = self.release_delay();
+        self.drive(true);
+        let de = &mut self.de;
+        let _release = OnDrop::new(|| set_de(de, false));
+
+        self.serial.write(buf).await?;
+        self.serial.flush().await?;
+
+        Timer::after(release_delay).await;
+        Ok(())
+    }
+}
@@ -8,7 +8,7 @@ use embassy_executor::Spawner;
 use embassy_sifive::gpio::gpio0::Pin16;
 use embassy_sifive::gpio::{NoInvert, IOF0};
 use embassy_sifive::time::U32Ext;
-use embassy_sifive::uart::Async;
+use embassy_sifive::uart::{self, Async};
 use embassy_time::{Duration, Timer};
 use embedded_hal::digital::OutputPin;
 use utils::pin;
@@ -17,6 +17,10 @@ extern crate panic_halt;
 
 const PERIOD1: u32 = 1000; // 1s
 
+embassy_sifive::bind_interrupts!(struct Irqs {
+    UART0 => uart::InterruptHandler<Uart0>;
+});
+
 async fn shared_sleep(period: u32) {
     Timer::after(Duration::from_millis(period.into())).await;
 }
@@ -57,9 +61,11 @@ async fn main(spawner: Spawner) {
     let serial = embassy_sifive::uart::Serial::new_async(
         p.UART0,
         (tx_pin.into_iof0(), rx_pin.into_iof0()),
-        embassy_sifive::time::Bps(115_200),
+        Irqs,
+        embassy_sifive::uart::Config::default(),
         clocks,
-    );
+    )
+    .unwrap();
 
     spawner.spawn(uart_task(serial)).unwrap();
     spawner
@@ -86,7 +92,7 @@ async fn uart_task(
                 // Convert the received byte to a string and print it
                 if let Ok(received) = core::str::from_utf8(&buffer[..1]) {
                     info!("Received: {}", received);
-                    serial.write(buffer[0]).await.unwrap();
+                    serial.write(&buffer).await.unwrap();
                 } else {
                     info!("Received non-UTF8 character");
                 }
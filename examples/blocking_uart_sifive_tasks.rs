@@ -82,7 +82,8 @@ async fn main(spawner: Spawner) {
         rx_pin,
         embassy_sifive::time::Bps(115_200),
         clocks,
-    );
+    )
+    .unwrap();
 
     spawner.spawn(my_task(1, PERIOD1)).unwrap();
     spawner.spawn(my_task(2, PERIOD2)).unwrap();
@@ -6,6 +6,7 @@
 ///   - `spi0_<x>` — SPI pins where `<x>` is one of (`sck`, `mosi`, `miso`, `ss0`, `ss2`, `ss3`)
 ///   - `i2c0_<x>` — I2C pins where `<x>` is one of (`sda`, `scl`)
 ///   - `uart0_<x>` — UART pins where `<x>` is one of (`tx`, `rx`)
+///   - `uart1_<x>` — UART pins where `<x>` is one of (`tx`, `rx`), FE310-G002 only
 ///   - `dig#` — Digital/physical pins on the board where `#` is from range 0..19
 ///   - `led_<x>` - Internal LED light pins where `<x>` is one of (`red`, `green`, `blue`)
 ///  - `pwmN_cmp#` - PWM channels where `N`` is from range 0..2 and `#` is from range 1..3
@@ -56,6 +57,12 @@ macro_rules! pin {
     ($gpio:ident, uart0_rx) => {
         $gpio.pin16
     };
+    ($gpio:ident, uart1_tx) => {
+        $gpio.pin18
+    };
+    ($gpio:ident, uart1_rx) => {
+        $gpio.pin23
+    };
     // digital/physical
     ($gpio:ident, dig0) => {
         $gpio.pin16
@@ -160,6 +167,7 @@ macro_rules! pin {
 ///   - `spi0_<x>` — SPI pins where `<x>` is one of (`sck`, `mosi`, `miso`, `ss0`, `ss2`, `ss3`)
 ///   - `i2c0_<x>` — I2C pins where `<x>` is one of (`sda`, `scl`)
 ///   - `uart0_<x>` — UART pins where `<x>` is one of (`tx`, `rx`)
+///   - `uart1_<x>` — UART pins where `<x>` is one of (`tx`, `rx`), FE310-G002 only
 ///   - `dig#` — Digital/physical pins on the board where `#` is from range 0..19
 ///   - `led_<x>` - Internal LED light pins `<x>` is one of (`red`, `green`, `blue`)
 ///
@@ -4,6 +4,7 @@ use core::{
     ptr,
 };
 use e310x::Uart0;
+use embedded_hal_nb::serial::Write;
 use nb::block;
 
 use embassy_sifive::{
@@ -13,15 +14,14 @@ use embassy_sifive::{
         NoInvert, IOF0,
     },
     time::Bps,
-    uart::{Blocking, Rx, Serial, Tx},
+    uart::{Blocking, Config, ConfigError, Rx, Serial, Tx},
 };
 
-/// Stdout implements the core::fmt::Write trait for hal::serial::Write
-/// implementations.struct SerialWrapper(Tx<Uart0, Pin17<IOF0<NoInvert>>>);
+/// Stdout implements the core::fmt::Write trait for embedded_hal_nb::serial::Write
+/// implementations.
+struct SerialWrapper<W>(W);
 
-struct SerialWrapper(Tx<Uart0, Pin17<IOF0<NoInvert>>, Blocking>);
-
-static mut STDOUT: Option<SerialWrapper> = None;
+static mut STDOUT: Option<SerialWrapper<Tx<Uart0, Pin17<IOF0<NoInvert>>, Blocking>>> = None;
 
 /// Configures stdout
 pub fn configure<X, Y>(
@@ -30,30 +30,34 @@ pub fn configure<X, Y>(
     rx: Pin16<Y>,
     baud_rate: Bps,
     clocks: Clocks,
-) -> Rx<Uart0, Pin16<IOF0<NoInvert>>, Blocking> {
+) -> Result<Rx<Uart0, Pin16<IOF0<NoInvert>>, Blocking>, ConfigError> {
     let tx = tx.into_iof0();
     let rx = rx.into_iof0();
-    let serial = Serial::new_blocking(uart, (tx, rx), baud_rate, clocks);
+    let config = Config {
+        baud_rate,
+        ..Default::default()
+    };
+    let serial = Serial::new_blocking(uart, (tx, rx), config, clocks)?;
     let (tx, rx) = serial.split();
 
     critical_section::with(|_| {
         unsafe { &mut *ptr::addr_of_mut!(STDOUT) }.replace(SerialWrapper(tx));
     });
-    rx
+    Ok(rx)
 }
 
-impl CoreWrite for SerialWrapper {
+impl<W: Write<u8>> CoreWrite for SerialWrapper<W> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         for byte in s.as_bytes() {
             if *byte == b'\n' {
-                let res = block!(self.0.blocking_write(b'\r'));
+                let res = block!(self.0.write(b'\r'));
 
                 if res.is_err() {
                     return Err(fmt::Error);
                 }
             }
 
-            let res = block!(self.0.blocking_write(*byte));
+            let res = block!(self.0.write(*byte));
 
             if res.is_err() {
                 return Err(fmt::Error);